@@ -0,0 +1,61 @@
+#[macro_use]
+extern crate criterion;
+extern crate sourcefile;
+
+use criterion::{black_box, Criterion};
+use sourcefile::SourceFile;
+
+/// A concatenation of `files` files of `lines` short lines each.
+fn build(files: usize, lines: usize) -> SourceFile {
+    let mut sourcefile = SourceFile::new();
+    let contents: String = (0..lines)
+        .map(|i| format!("let x{} = {};\n", i, i))
+        .collect();
+    for i in 0..files {
+        sourcefile.add_file_raw(format!("file{}.rs", i), contents.as_str());
+    }
+    sourcefile
+}
+
+fn resolve(c: &mut Criterion) {
+    let sourcefile = build(100, 100);
+    let len = sourcefile.contents.len();
+
+    c.bench_function("resolve_offset start", |b| {
+        b.iter(|| sourcefile.resolve_offset(black_box(0)))
+    });
+    c.bench_function("resolve_offset middle", |b| {
+        b.iter(|| sourcefile.resolve_offset(black_box(len / 2)))
+    });
+    c.bench_function("resolve_offset end", |b| {
+        b.iter(|| sourcefile.resolve_offset(black_box(len - 1)))
+    });
+    c.bench_function("resolve_offset_id middle", |b| {
+        b.iter(|| sourcefile.resolve_offset_id(black_box(len / 2)))
+    });
+    c.bench_function("resolve_offset_span", |b| {
+        b.iter(|| sourcefile.resolve_offset_span(black_box(len / 2), black_box(len / 2 + 20)))
+    });
+}
+
+/// Resolve offsets spread over the text, at sizes either side of where resolution switches from
+/// searching all lines to finding the file first.
+fn resolve_by_size(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resolve_offset by lines");
+    for &(files, lines) in &[(10, 100), (40, 100), (100, 100), (100, 1000), (1000, 1000)] {
+        let sourcefile = build(files, lines);
+        let len = sourcefile.contents.len();
+        let offsets: Vec<usize> = (0..1024).map(|i| i * 7919 % len).collect();
+        group.bench_function(format!("{}", files * lines), |b| {
+            b.iter(|| {
+                for &offset in &offsets {
+                    black_box(sourcefile.resolve_offset(black_box(offset)));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, resolve, resolve_by_size);
+criterion_main!(benches);
@@ -0,0 +1,167 @@
+//! Copies of the source text with markers inserted around spans.
+
+use std::cmp::Reverse;
+
+use {FileId, SourceFile};
+
+impl SourceFile {
+    /// Make a copy of `contents` with markers inserted around each of `spans`.
+    ///
+    /// `markers` is called once per span with its index in `spans`, and returns the opening and
+    /// closing markers for it, e.g. `("«", "»")` or `("<b>", "</b>")`. Overlapping spans are
+    /// closed and reopened where needed so the markers always nest properly.
+    ///
+    /// Returns `None` if a span is reversed, out of bounds, or not on character boundaries.
+    pub fn annotate_contents<F, S>(&self, spans: &[(usize, usize)], markers: F) -> Option<String>
+    where
+        F: FnMut(usize) -> (S, S),
+        S: AsRef<str>,
+    {
+        annotate(&self.contents, 0, spans, markers)
+    }
+
+    /// Like `annotate_contents`, but only copies the text of one file.
+    ///
+    /// Spans are still global offsets into `contents`, and must lie within the file.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn annotate_file<F, S>(
+        &self,
+        id: FileId,
+        spans: &[(usize, usize)],
+        markers: F,
+    ) -> Option<String>
+    where
+        F: FnMut(usize) -> (S, S),
+        S: AsRef<str>,
+    {
+        let range = self.file_span(id);
+        annotate(&self.contents[range.clone()], range.start, spans, markers)
+    }
+}
+
+/// Annotate `text`, which starts at offset `base` of the spans' coordinates.
+fn annotate<F, S>(
+    text: &str,
+    base: usize,
+    spans: &[(usize, usize)],
+    mut markers: F,
+) -> Option<String>
+where
+    F: FnMut(usize) -> (S, S),
+    S: AsRef<str>,
+{
+    let mut local = Vec::with_capacity(spans.len());
+    for &(start, end) in spans {
+        let (start, end) = (start.checked_sub(base)?, end.checked_sub(base)?);
+        if end < start || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            return None;
+        }
+        local.push((start, end));
+    }
+    let markers: Vec<(S, S)> = (0..spans.len()).map(&mut markers).collect();
+
+    // Open spans in start order, outermost first.
+    let mut order: Vec<usize> = (0..local.len()).collect();
+    order.sort_by_key(|&idx| (local[idx].0, Reverse(local[idx].1)));
+    let mut bounds: Vec<usize> = local
+        .iter()
+        .flat_map(|&(start, end)| [start, end])
+        .collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let extra: usize = markers
+        .iter()
+        .map(|(open, close)| open.as_ref().len() + close.as_ref().len())
+        .sum();
+    let mut out = String::with_capacity(text.len() + extra);
+    // The spans currently open, innermost last.
+    let mut stack: Vec<usize> = Vec::new();
+    let mut next = 0;
+    let mut pos = 0;
+    for bound in bounds {
+        out.push_str(&text[pos..bound]);
+        pos = bound;
+
+        // Close everything down to the outermost span ending here, then reopen the ones that
+        // continue.
+        if let Some(outermost) = stack.iter().position(|&idx| local[idx].1 == bound) {
+            for &idx in stack[outermost..].iter().rev() {
+                out.push_str(markers[idx].1.as_ref());
+            }
+            let reopen: Vec<usize> = stack
+                .drain(outermost..)
+                .filter(|&idx| local[idx].1 != bound)
+                .collect();
+            for idx in reopen {
+                out.push_str(markers[idx].0.as_ref());
+                stack.push(idx);
+            }
+        }
+
+        while next < order.len() && local[order[next]].0 == bound {
+            let idx = order[next];
+            next += 1;
+            out.push_str(markers[idx].0.as_ref());
+            if local[idx].1 == bound {
+                out.push_str(markers[idx].1.as_ref());
+            } else {
+                stack.push(idx);
+            }
+        }
+    }
+    out.push_str(&text[pos..]);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    fn brackets(idx: usize) -> (String, String) {
+        (format!("<{}>", idx), format!("</{}>", idx))
+    }
+
+    #[test]
+    fn nested_and_overlapping() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "abcdefgh\n");
+        assert_eq!(
+            sourcefile
+                .annotate_contents(&[(1, 3), (0, 4)], brackets)
+                .unwrap(),
+            "<1>a<0>bc</0>d</1>efgh\n"
+        );
+        assert_eq!(
+            sourcefile
+                .annotate_contents(&[(0, 4), (2, 6)], brackets)
+                .unwrap(),
+            "<0>ab<1>cd</1></0><1>ef</1>gh\n"
+        );
+        assert_eq!(
+            sourcefile
+                .annotate_contents(&[(2, 2)], |_| ("«", "»"))
+                .unwrap(),
+            "ab«»cdefgh\n"
+        );
+        assert!(sourcefile.annotate_contents(&[(3, 2)], brackets).is_none());
+        assert!(sourcefile
+            .annotate_contents(&[(0, 100)], brackets)
+            .is_none());
+    }
+
+    #[test]
+    fn one_file() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "first\n");
+        let b = sourcefile.add_file_raw("b", "second\n");
+        assert_eq!(
+            sourcefile.annotate_file(b, &[(6, 9)], brackets).unwrap(),
+            "<0>sec</0>ond\n"
+        );
+        assert!(sourcefile.annotate_file(b, &[(0, 1)], brackets).is_none());
+    }
+}
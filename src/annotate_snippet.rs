@@ -0,0 +1,71 @@
+//! Rendering spans with `annotate-snippets`.
+
+use annotate_snippets::{Level, Snippet};
+
+use {SourceFile, Span};
+
+impl SourceFile {
+    /// Make an `annotate_snippets::Snippet` of the lines `span` covers, with `message` as the
+    /// label of an annotation at `level` on the span.
+    ///
+    /// The snippet's origin is the name of the file the span starts in, and its lines are
+    /// numbered from that file's. Add it to a `Message` to render it. Returns `None` if either
+    /// end of the span isn't a position in this `SourceFile`, as checked by `verify_position`,
+    /// or the span is reversed.
+    pub fn annotate_snippet<'a>(
+        &'a self,
+        span: &Span,
+        level: Level,
+        message: &'a str,
+    ) -> Option<Snippet<'a>> {
+        if !self.verify_position(&span.start) || !self.verify_position(&span.end) {
+            return None;
+        }
+        let (start, end) = (span.start.offset, span.end.offset);
+        if end < start {
+            return None;
+        }
+        let source_start = start - span.start.col;
+        let source_end = self.line_range(end)?.end;
+        let filename = &self.file_names[self.locate(start)?.2];
+        Some(
+            Snippet::source(&self.contents[source_start..source_end])
+                .line_start(span.start.line + 1)
+                .origin(filename)
+                .annotation(
+                    level
+                        .span(start - source_start..end - source_start)
+                        .label(message),
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use annotate_snippets::{Level, Renderer};
+
+    use SourceFile;
+
+    #[test]
+    fn annotate_snippet() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\n");
+        sourcefile.add_file_raw("b", "let x = 1;\nlet y = two;\n");
+        let span = sourcefile.resolve_offset_span(23, 26).unwrap();
+        let snippet = sourcefile
+            .annotate_snippet(&span, Level::Error, "not a number")
+            .unwrap();
+        let message = Level::Error.title("mismatched types").snippet(snippet);
+        let out = Renderer::plain().render(message).to_string();
+        assert!(out.contains("--> b:2:9"), "{}", out);
+        assert!(out.contains("2 | let y = two;"), "{}", out);
+        assert!(out.contains("^^^ not a number"), "{}", out);
+
+        let mut stale = span;
+        stale.end.offset += 1;
+        assert!(sourcefile
+            .annotate_snippet(&stale, Level::Error, "")
+            .is_none());
+    }
+}
@@ -0,0 +1,122 @@
+//! Rendering `ariadne` reports from a `SourceFile`.
+
+use std::fmt;
+use std::ops::Range;
+
+use ariadne::{Cache, Source};
+
+use {FileId, SourceFile};
+
+/// An `ariadne::Cache` of the files in a `SourceFile`, keyed by `FileId`, see
+/// `SourceFile::ariadne_cache`.
+///
+/// Each file's `Source` borrows its text from `contents`, and is built the first time the
+/// file is fetched.
+pub struct AriadneCache<'a> {
+    sourcefile: &'a SourceFile,
+    sources: Vec<Option<Source<&'a str>>>,
+}
+
+impl<'a> fmt::Debug for AriadneCache<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AriadneCache")
+            .field("files", &self.sources.len())
+            .field(
+                "fetched",
+                &self
+                    .sources
+                    .iter()
+                    .filter(|source| source.is_some())
+                    .count(),
+            )
+            .finish()
+    }
+}
+
+impl<'a> Cache<FileId> for AriadneCache<'a> {
+    type Storage = &'a str;
+
+    fn fetch(&mut self, id: &FileId) -> Result<&Source<&'a str>, impl fmt::Debug> {
+        let sourcefile = self.sourcefile;
+        match self.sources.get_mut(id.0) {
+            Some(source) => {
+                Ok(source.get_or_insert_with(|| Source::from(sourcefile.file_contents(*id))))
+            }
+            None => Err(format!("unknown file {:?}", id)),
+        }
+    }
+
+    fn display<'b>(&self, id: &'b FileId) -> Option<impl fmt::Display + 'b> {
+        self.sourcefile.try_file_name(*id).ok().map(str::to_owned)
+    }
+}
+
+impl SourceFile {
+    /// An `ariadne::Cache` of the files, for rendering reports with spans from `ariadne_span`.
+    pub fn ariadne_cache<'a>(&'a self) -> AriadneCache<'a> {
+        AriadneCache {
+            sourcefile: self,
+            sources: (0..self.file_names.len()).map(|_| None).collect(),
+        }
+    }
+
+    /// The span `start..end` of `contents` as an `ariadne` span: the file it starts in, and its
+    /// byte range within that file.
+    ///
+    /// Ariadne counts chars by default, so render with
+    /// `Config::default().with_index_type(IndexType::Byte)`. Returns `None` if the span is
+    /// reversed or runs past the end of its file. A span at the very end of `contents` is in
+    /// the last file.
+    pub fn ariadne_span(&self, start: usize, end: usize) -> Option<(FileId, Range<usize>)> {
+        if end < start {
+            return None;
+        }
+        let (_, _, file_idx) = self
+            .locate(start)
+            .or_else(|| self.locate(start.checked_sub(1)?).filter(|_| start == end))?;
+        let file = self.file_span(FileId(file_idx));
+        if end > file.end {
+            return None;
+        }
+        Some((FileId(file_idx), start - file.start..end - file.start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ariadne::{Cache, Config, IndexType, Label, Report, ReportKind};
+
+    use {FileId, SourceFile};
+
+    #[test]
+    fn ariadne_report() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\n");
+        let b = sourcefile.add_file_raw("b", "é = two\nthree\n");
+        assert_eq!(sourcefile.ariadne_span(9, 12), Some((b, 5..8)));
+        assert_eq!(sourcefile.ariadne_span(18, 18), Some((b, 14..14)));
+        assert_eq!(sourcefile.ariadne_span(2, 6), None);
+        assert_eq!(sourcefile.ariadne_span(12, 9), None);
+
+        let mut cache = sourcefile.ariadne_cache();
+        assert!(cache.fetch(&FileId(2)).is_err());
+        assert_eq!(cache.display(&b).unwrap().to_string(), "b");
+
+        let span = sourcefile.ariadne_span(9, 12).unwrap();
+        let mut out = Vec::new();
+        Report::build(ReportKind::Error, span.clone())
+            .with_config(
+                Config::default()
+                    .with_color(false)
+                    .with_index_type(IndexType::Byte),
+            )
+            .with_message("not a number")
+            .with_label(Label::new(span).with_message("here"))
+            .finish()
+            .write(&mut cache, &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("b:1:5"), "{}", out);
+        assert!(out.contains("é = two"), "{}", out);
+    }
+}
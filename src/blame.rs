@@ -0,0 +1,64 @@
+//! Caller-supplied per-line annotations, such as `git blame` commit ids.
+
+use {FileId, Position, SourceFile};
+
+impl SourceFile {
+    /// Attach an annotation to each line of a file, in order, replacing any already set.
+    ///
+    /// The annotations are opaque to this crate; a typical use is the commit that last changed
+    /// each line, so that diagnostics can say where a problem was introduced. Lines past the end
+    /// of `annotations` have none.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn set_line_annotations<I, S>(&mut self, id: FileId, annotations: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.file_annotations[id.0] = annotations.into_iter().map(Into::into).collect();
+    }
+
+    /// The annotation of a line of a file, if it has one.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn line_annotation(&self, id: FileId, line: usize) -> Option<&str> {
+        self.file_annotations[id.0].get(line).map(String::as_str)
+    }
+
+    /// Like `resolve_offset`, but also returns the annotation of the resolved line.
+    pub fn resolve_offset_annotated<'a>(
+        &'a self,
+        offset: usize,
+    ) -> Option<(Position<'a>, Option<&'a str>)> {
+        let pos = self.resolve_offset(offset)?;
+        let (line_idx, _) = self.locate_line(offset)?;
+        let (file_idx, _) = self.locate_file(line_idx);
+        Some((pos, self.line_annotation(FileId(file_idx), pos.line)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn annotations() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\n");
+        let b = sourcefile.add_file_raw("b", "two\nthree\nfour\n");
+        sourcefile.set_line_annotations(b, vec!["abc123", "def456"]);
+
+        assert_eq!(sourcefile.line_annotation(b, 1), Some("def456"));
+        assert_eq!(sourcefile.line_annotation(b, 2), None);
+        let (pos, annotation) = sourcefile.resolve_offset_annotated(9).unwrap();
+        assert_eq!(
+            (pos.filename, pos.line, annotation),
+            ("b", 1, Some("def456"))
+        );
+        assert_eq!(sourcefile.resolve_offset_annotated(1).unwrap().1, None);
+    }
+}
@@ -0,0 +1,64 @@
+//! Dropping the byte order mark some editors put at the start of UTF-8 files.
+
+use SourceFile;
+
+/// The byte order mark, which in UTF-8 is the 3 bytes `EF BB BF`.
+const BOM: char = '\u{FEFF}';
+
+impl SourceFile {
+    /// Whether to drop a byte order mark from the start of files as they are added. Off by
+    /// default, so `contents` holds exactly the text that was added.
+    ///
+    /// A dropped mark isn't in `contents`, so offsets agree with tools that ignore it, and
+    /// `original_offset` still maps them back to the bytes the file was added with. Option
+    /// snippets and regions copied with `duplicate_region` are never stripped.
+    pub fn set_strip_bom(&mut self, strip: bool) {
+        self.strip_bom = strip;
+    }
+
+    /// Whether a byte order mark is dropped from files as they are added, see `set_strip_bom`.
+    #[inline]
+    pub fn strips_bom(&self) -> bool {
+        self.strip_bom
+    }
+
+    /// The length in bytes of the byte order mark that will be dropped from the start of
+    /// `contents`, if any.
+    pub(crate) fn bom_len(&self, contents: &str) -> usize {
+        if self.strips_bom() && contents.starts_with(BOM) {
+            BOM.len_utf8()
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn strip_bom() {
+        let mut sourcefile = SourceFile::new();
+        let kept = sourcefile.add_file_raw("kept", "\u{FEFF}x\n");
+        assert_eq!(sourcefile.file_contents(kept), "\u{FEFF}x\n");
+        assert!(!sourcefile.strips_bom());
+        sourcefile.clear();
+
+        sourcefile.set_strip_bom(true);
+        assert!(sourcefile.strips_bom());
+        let a = sourcefile.add_file_raw("a", "\u{FEFF}one\n");
+        let b = sourcefile.add_file_raw("b", "\u{FEFF}\u{FEFF}two\n");
+        assert_eq!(sourcefile.contents, "one\n\u{FEFF}two\n");
+        assert_eq!(sourcefile.original_offset(1), Some((a, 4)));
+        let two = sourcefile.resolve_offset(7).unwrap();
+        assert_eq!((two.filename, two.col), ("b", 3));
+        assert_eq!(sourcefile.original_offset(7), Some((b, 6)));
+        assert!(sourcefile.try_add_file_raw("bom", "\u{FEFF}").is_err());
+
+        sourcefile.set_strip_bom(false);
+        let c = sourcefile.add_file_raw("c", "\u{FEFF}x\n");
+        assert_eq!(sourcefile.file_contents(c), "\u{FEFF}x\n");
+        assert_eq!(sourcefile.original_offset(14), Some((c, 3)));
+    }
+}
@@ -0,0 +1,115 @@
+//! Rendering diagnostics with `codespan-reporting`.
+
+use std::ops::Range;
+
+use codespan_reporting::files::{Error, Files};
+
+use {FileId, SourceFile};
+
+/// Byte indices are relative to the start of each file, as for `codespan_reporting`'s own
+/// `SimpleFiles`, so subtract `file_span(id).start` from offsets into `contents`.
+///
+/// As in `SimpleFiles`, a file that is empty or ends with a newline has an extra empty line at
+/// its end, so a diagnostic can point at the end of the file.
+impl<'a> Files<'a> for SourceFile {
+    type FileId = FileId;
+    type Name = &'a str;
+    type Source = &'a str;
+
+    fn name(&'a self, id: FileId) -> Result<&'a str, Error> {
+        self.try_file_name(id).map_err(|_| Error::FileMissing)
+    }
+
+    fn source(&'a self, id: FileId) -> Result<&'a str, Error> {
+        self.name(id)?;
+        Ok(self.file_contents(id))
+    }
+
+    fn line_index(&'a self, id: FileId, byte_index: usize) -> Result<usize, Error> {
+        self.name(id)?;
+        let span = self.file_span(id);
+        let lines = self.file_line_indices(id);
+        if byte_index > span.len() {
+            return Err(Error::IndexTooLarge {
+                given: byte_index,
+                max: span.len(),
+            });
+        }
+        let offset = span.start + byte_index;
+        let line = self.line_ends[lines].partition_point(|&end| end <= offset);
+        Ok(line.min(codespan_line_count(self, id) - 1))
+    }
+
+    fn line_range(&'a self, id: FileId, line_index: usize) -> Result<Range<usize>, Error> {
+        self.name(id)?;
+        let span = self.file_span(id);
+        let lines = self.file_line_indices(id);
+        if line_index < lines.len() {
+            let line_idx = lines.start + line_index;
+            Ok(self.line_offset(line_idx) - span.start..self.line_ends[line_idx] - span.start)
+        } else if line_index < codespan_line_count(self, id) {
+            Ok(span.len()..span.len())
+        } else {
+            Err(Error::LineTooLarge {
+                given: line_index,
+                max: codespan_line_count(self, id) - 1,
+            })
+        }
+    }
+}
+
+/// The number of lines `codespan_reporting` sees in a file, counting the empty line after a
+/// final newline.
+fn codespan_line_count(sourcefile: &SourceFile, id: FileId) -> usize {
+    let contents = sourcefile.file_contents(id);
+    let extra = contents.is_empty() || contents.ends_with('\n');
+    sourcefile.line_count(id) + extra as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan_reporting::files::{Files, Location, SimpleFile};
+
+    use {FileId, SourceFile};
+
+    #[test]
+    fn codespan_files() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo");
+        let b = sourcefile.add_file_raw("b", "é\r\n\nx\n");
+        let empty = sourcefile.add_file_raw("empty", "");
+        // Some inherent methods have the same names, so call these through the trait.
+        assert_eq!(Files::name(&sourcefile, b).unwrap(), "b");
+        assert_eq!(Files::source(&sourcefile, b).unwrap(), "é\r\n\nx\n");
+        assert_eq!(
+            Files::location(&sourcefile, b, 5).unwrap(),
+            Location {
+                line_number: 3,
+                column_number: 1
+            }
+        );
+
+        // Line lookups match `codespan_reporting`'s own, including at the ends of files.
+        for file in sourcefile.files() {
+            let simple = SimpleFile::new(file.name, file.contents);
+            for byte_index in 0..=file.contents.len() {
+                assert_eq!(
+                    Files::line_index(&sourcefile, file.id, byte_index).unwrap(),
+                    simple.line_index((), byte_index).unwrap()
+                );
+            }
+            let mut line_index = 0;
+            while let Ok(range) = simple.line_range((), line_index) {
+                assert_eq!(
+                    Files::line_range(&sourcefile, file.id, line_index).unwrap(),
+                    range
+                );
+                line_index += 1;
+            }
+            assert!(Files::line_range(&sourcefile, file.id, line_index).is_err());
+        }
+        assert_eq!(Files::line_range(&sourcefile, empty, 0).unwrap(), 0..0);
+        assert!(Files::line_index(&sourcefile, b, 8).is_err());
+        assert!(Files::name(&sourcefile, FileId(3)).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! Counting columns in units other than bytes.
+
+#[cfg(feature = "unicode-segmentation")]
+use unicode_segmentation::UnicodeSegmentation;
+
+#[cfg(feature = "unicode-width")]
+use unicode_width::UnicodeWidthChar;
+
+use {Position, SourceFile};
+
+/// What a column number counts.
+///
+/// `Position::col` always counts bytes, so that it can be added to the offset of the start of
+/// its line. Use `SourceFile::resolve_offset_with_encoding` to get it in another unit.
+///
+/// More encodings may be added, some behind features, so matches need a wildcard arm.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum ColumnEncoding {
+    /// Bytes of UTF-8, as gcc and clang count.
+    Bytes,
+    /// Unicode scalar values (`char`s), as tsc counts, so that column 5 is the fifth char.
+    Chars,
+    /// UTF-16 code units, as the Language Server Protocol counts by default. Chars outside the
+    /// Basic Multilingual Plane, like most emoji, take two.
+    Utf16,
+    /// Extended grapheme clusters, so that e.g. `e` followed by a combining accent is one
+    /// column, as editors and terminals usually show it. A byte in the middle of a cluster, like
+    /// the accent, gets the column after the cluster's. Needs the `unicode-segmentation` feature.
+    #[cfg(feature = "unicode-segmentation")]
+    Graphemes,
+    /// Terminal cells, so that wide CJK chars take two and zero-width chars like joiners and
+    /// combining accents take none, for lining carets up under source. Control chars take none.
+    /// Needs the `unicode-width` feature.
+    #[cfg(feature = "unicode-width")]
+    Width,
+}
+
+impl ColumnEncoding {
+    /// The column of the byte at `byte` in `line`, counted in this encoding.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `byte` is past the end of `line` or not on a char boundary.
+    pub fn column(&self, line: &str, byte: usize) -> usize {
+        let before = &line[..byte];
+        match *self {
+            ColumnEncoding::Bytes => byte,
+            ColumnEncoding::Chars => before.chars().count(),
+            ColumnEncoding::Utf16 => before.chars().map(char::len_utf16).sum(),
+            #[cfg(feature = "unicode-segmentation")]
+            ColumnEncoding::Graphemes => before.graphemes(true).count(),
+            #[cfg(feature = "unicode-width")]
+            ColumnEncoding::Width => before.chars().map(char_width).sum(),
+        }
+    }
+
+    /// The column of the byte at `byte` in `line`, counted in this encoding, with each tab
+    /// advancing to the next multiple of `tab_width`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `byte` is past the end of `line` or not on a char boundary,
+    /// or if `tab_width` is 0.
+    pub fn column_with_tabs(&self, line: &str, byte: usize, tab_width: usize) -> usize {
+        assert!(tab_width > 0, "tab width must be at least 1");
+        let mut col = 0;
+        for (idx, between_tabs) in line[..byte].split('\t').enumerate() {
+            if idx > 0 {
+                col = (col / tab_width + 1) * tab_width;
+            }
+            col += self.column(between_tabs, between_tabs.len());
+        }
+        col
+    }
+
+    /// The byte in `line` at a column counted in this encoding, the reverse of `column`.
+    ///
+    /// The column may be just past the end of `line`. Returns `None` if it is further, or inside
+    /// a char.
+    pub fn byte(&self, line: &str, col: usize) -> Option<usize> {
+        match *self {
+            ColumnEncoding::Bytes => {
+                if line.is_char_boundary(col) {
+                    Some(col)
+                } else {
+                    None
+                }
+            }
+            ColumnEncoding::Chars => line
+                .char_indices()
+                .map(|(byte, _)| byte)
+                .chain(Some(line.len()))
+                .nth(col),
+            ColumnEncoding::Utf16 => byte_at_units(line, col, char::len_utf16),
+            #[cfg(feature = "unicode-width")]
+            ColumnEncoding::Width => byte_at_units(line, col, char_width),
+            #[cfg(feature = "unicode-segmentation")]
+            ColumnEncoding::Graphemes => line
+                .grapheme_indices(true)
+                .map(|(byte, _)| byte)
+                .chain(Some(line.len()))
+                .nth(col),
+        }
+    }
+}
+
+/// The byte in `line` where the units of the chars before it add up to `col`, if there is one.
+///
+/// Where zero-unit chars make several bytes share a column, the first is used.
+fn byte_at_units<F: Fn(char) -> usize>(line: &str, col: usize, units_of: F) -> Option<usize> {
+    let mut units = 0;
+    for (byte, c) in line.char_indices() {
+        if units >= col {
+            return if units == col { Some(byte) } else { None };
+        }
+        units += units_of(c);
+    }
+    if units == col {
+        Some(line.len())
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "unicode-width")]
+fn char_width(c: char) -> usize {
+    UnicodeWidthChar::width(c).unwrap_or(0)
+}
+
+impl SourceFile {
+    /// Get the file, line, and col position of a byte offset, as `resolve_offset` does but with
+    /// the column counted in `encoding`.
+    ///
+    /// The line's text is scanned up to the offset for encodings other than `Bytes`. Returns
+    /// `None` if the offset is past the end of `contents`, or in the middle of a char, where
+    /// other encodings have no column, unlike `resolve_offset`.
+    pub fn resolve_offset_with_encoding<'a>(
+        &'a self,
+        offset: usize,
+        encoding: ColumnEncoding,
+    ) -> Option<Position<'a>> {
+        let mut position = self.resolve_offset(offset)?;
+        let line = &self.contents[offset - position.col..];
+        if !line.is_char_boundary(position.col) {
+            return None;
+        }
+        position.col = encoding.column(line, position.col);
+        Some(position)
+    }
+
+    /// Expand tabs to the given width in columns from `expanded_col` and `display_col`, or stop
+    /// expanding them with `None`. Tabs aren't expanded by default.
+    ///
+    /// Other column queries, such as `resolve_offset_with_encoding`, are unaffected, since their
+    /// columns are for slicing text or for tools that count tabs as one.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `tab_width` is `Some(0)`.
+    pub fn set_tab_width(&mut self, tab_width: Option<usize>) {
+        assert!(tab_width != Some(0), "tab width must be at least 1");
+        self.tab_width = tab_width;
+    }
+
+    /// The width tabs are expanded to, if set with `set_tab_width`.
+    #[inline]
+    pub fn tab_width(&self) -> Option<usize> {
+        self.tab_width
+    }
+
+    /// The column of a byte offset as printed under its source line, counted in `encoding` with
+    /// tabs expanded if a tab width is set, or `None` if the offset is past the end of
+    /// `contents` or in the middle of a char.
+    ///
+    /// This is the column compilers put carets under.
+    pub fn expanded_col(&self, offset: usize, encoding: ColumnEncoding) -> Option<usize> {
+        let position = self.resolve_offset(offset)?;
+        let line = &self.contents[offset - position.col..];
+        if !line.is_char_boundary(position.col) {
+            return None;
+        }
+        Some(match self.tab_width {
+            Some(tab_width) => encoding.column_with_tabs(line, position.col, tab_width),
+            None => encoding.column(line, position.col),
+        })
+    }
+
+    /// The column of a byte offset in terminal cells, for lining up carets under the source
+    /// line, or `None` if the offset is past the end of `contents` or in the middle of a char.
+    /// Tabs are expanded as for `expanded_col`.
+    #[cfg(feature = "unicode-width")]
+    pub fn display_col(&self, offset: usize) -> Option<usize> {
+        self.expanded_col(offset, ColumnEncoding::Width)
+    }
+
+    /// Get the file, line, and col position of a byte offset with the column in UTF-16 code
+    /// units, for sending to language server clients.
+    ///
+    /// Returns `None` for an offset in the middle of a char, as `resolve_offset_with_encoding`
+    /// does, since no UTF-16 column points there.
+    #[inline]
+    pub fn resolve_offset_utf16<'a>(&'a self, offset: usize) -> Option<Position<'a>> {
+        self.resolve_offset_with_encoding(offset, ColumnEncoding::Utf16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColumnEncoding;
+    use SourceFile;
+
+    #[test]
+    fn encodings() {
+        let line = "aé•b";
+        assert_eq!(ColumnEncoding::Bytes.column(line, 3), 3);
+        assert_eq!(ColumnEncoding::Chars.column(line, 6), 3);
+        assert_eq!(ColumnEncoding::Chars.byte(line, 3), Some(6));
+        assert_eq!(ColumnEncoding::Chars.byte(line, 4), Some(7));
+        assert_eq!(ColumnEncoding::Chars.byte(line, 5), None);
+        assert_eq!(ColumnEncoding::Bytes.byte(line, 2), None);
+
+        let emoji = "a😀b";
+        assert_eq!(ColumnEncoding::Utf16.column(emoji, 5), 3);
+        assert_eq!(ColumnEncoding::Utf16.column(line, 6), 3);
+        assert_eq!(ColumnEncoding::Utf16.byte(emoji, 3), Some(5));
+        assert_eq!(ColumnEncoding::Utf16.byte(emoji, 4), Some(6));
+        // Between the two halves of the surrogate pair.
+        assert_eq!(ColumnEncoding::Utf16.byte(emoji, 2), None);
+        assert_eq!(ColumnEncoding::Utf16.byte(emoji, 5), None);
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn graphemes() {
+        // `e` and a combining acute accent, then a flag made of two regional indicators.
+        let line = "ae\u{301}\u{1F1EC}\u{1F1E7}b";
+        assert_eq!(ColumnEncoding::Graphemes.column(line, 4), 2);
+        assert_eq!(ColumnEncoding::Graphemes.column(line, 12), 3);
+        assert_eq!(ColumnEncoding::Graphemes.byte(line, 2), Some(4));
+        assert_eq!(ColumnEncoding::Graphemes.byte(line, 3), Some(12));
+        assert_eq!(ColumnEncoding::Graphemes.byte(line, 5), None);
+        assert_eq!(ColumnEncoding::Chars.column(line, 12), 5);
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[test]
+    fn width() {
+        let line = "a中\u{301}b";
+        assert_eq!(ColumnEncoding::Width.column(line, 4), 3);
+        assert_eq!(ColumnEncoding::Width.column(line, 6), 3);
+        assert_eq!(ColumnEncoding::Width.byte(line, 3), Some(4));
+        assert_eq!(ColumnEncoding::Width.byte(line, 2), None);
+        assert_eq!(ColumnEncoding::Width.byte(line, 4), Some(7));
+
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "x\n名前 = 1\n");
+        assert_eq!(sourcefile.display_col(9), Some(5));
+        assert_eq!(sourcefile.display_col(3), None);
+        assert_eq!(sourcefile.resolve_offset(9).unwrap().col, 7);
+    }
+
+    #[test]
+    fn tabs() {
+        assert_eq!(ColumnEncoding::Chars.column_with_tabs("\tab\tc", 5, 4), 9);
+        assert_eq!(ColumnEncoding::Chars.column_with_tabs("abcd\te", 5, 4), 8);
+        assert_eq!(ColumnEncoding::Chars.column_with_tabs("é\t", 3, 8), 8);
+
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "\tx\n");
+        assert_eq!(sourcefile.expanded_col(1, ColumnEncoding::Chars), Some(1));
+        sourcefile.set_tab_width(Some(8));
+        assert_eq!(sourcefile.tab_width(), Some(8));
+        assert_eq!(sourcefile.expanded_col(1, ColumnEncoding::Chars), Some(8));
+        assert_eq!(sourcefile.resolve_offset(1).unwrap().col, 1);
+
+        let b = sourcefile.add_file_raw("b", "é\tx\n");
+        let start = sourcefile.file_span(b).start;
+        assert_eq!(
+            sourcefile.expanded_col(start + 3, ColumnEncoding::Bytes),
+            Some(8)
+        );
+        // In the middle of the `é`.
+        assert_eq!(
+            sourcefile.expanded_col(start + 1, ColumnEncoding::Bytes),
+            None
+        );
+        sourcefile.set_tab_width(None);
+        assert_eq!(
+            sourcefile.expanded_col(start + 1, ColumnEncoding::Chars),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve_with_encoding() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "x\n");
+        sourcefile.add_file_raw("b", "let é = 1;\n");
+        let bytes = sourcefile.resolve_offset(9).unwrap();
+        let chars = sourcefile
+            .resolve_offset_with_encoding(9, ColumnEncoding::Chars)
+            .unwrap();
+        assert_eq!((bytes.line, bytes.col), (0, 7));
+        assert_eq!(
+            (chars.filename, chars.line, chars.col, chars.offset),
+            ("b", 0, 6, 9)
+        );
+        assert_eq!(
+            sourcefile.resolve_offset_with_encoding(9, ColumnEncoding::Bytes),
+            Some(bytes)
+        );
+        // In the middle of the `é`.
+        assert_eq!(
+            sourcefile.resolve_offset_with_encoding(7, ColumnEncoding::Bytes),
+            None
+        );
+        assert_eq!(
+            sourcefile.resolve_offset_with_encoding(7, ColumnEncoding::Chars),
+            None
+        );
+
+        sourcefile.add_file_raw("c", "// 😀 ok\n");
+        let ok = sourcefile.resolve_offset_utf16(22).unwrap();
+        assert_eq!((ok.filename, ok.col), ("c", 6));
+        // Inside the emoji.
+        assert_eq!(sourcefile.resolve_offset_utf16(19), None);
+    }
+}
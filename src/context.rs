@@ -0,0 +1,184 @@
+//! Bundles of information about a span, for consumers that render or inspect source text.
+
+use std::ops::Range;
+
+use lines::trim_line_ending;
+use {SourceFile, Span};
+
+/// Everything a renderer typically needs to know about a span, see `SourceFile::span_context`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SpanContext<'a> {
+    /// The resolved start and end of the span.
+    pub span: Span<'a>,
+    /// The source text covered by the span.
+    pub text: &'a str,
+    /// The full text of every line the span touches, including line endings.
+    pub lines: &'a str,
+    /// The byte range in `contents` of each touched line.
+    pub line_ranges: Vec<Range<usize>>,
+    /// For each touched line, the byte range within that line covered by the span.
+    pub highlights: Vec<Range<usize>>,
+}
+
+/// One line of a `Snippet`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SnippetLine<'a> {
+    /// The 0-indexed line number, local to the line's file.
+    pub line: usize,
+    /// The text of the line, without its line ending.
+    pub text: &'a str,
+    /// The byte range within `text` covered by the span, or `None` for a line of context.
+    pub highlight: Option<Range<usize>>,
+}
+
+/// The lines a span covers and some lines around them, see `SourceFile::snippet`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Snippet<'a> {
+    /// The resolved start and end of the span.
+    pub span: Span<'a>,
+    /// The lines, in order.
+    pub lines: Vec<SnippetLine<'a>>,
+}
+
+impl SourceFile {
+    /// Collect the lines a span covers, plus up to `context_lines` lines either side, as for
+    /// showing the span in an error message.
+    ///
+    /// Context lines are only taken from the file the span starts in (before it) and the file it
+    /// ends in (after it). Highlights are clamped to the text of their line, so a span covering a
+    /// line ending has its highlight end at the end of the text. Returns `None` as
+    /// `span_context` does.
+    pub fn snippet<'a>(
+        &'a self,
+        start: usize,
+        end: usize,
+        context_lines: usize,
+    ) -> Option<Snippet<'a>> {
+        let context = self.span_context(start, end)?;
+        let (first_line, _) = self.locate_line(start)?;
+        let last_line = first_line + context.line_ranges.len() - 1;
+        let first_file_start = self.locate_file(first_line).1;
+        let last_file_end = self.file_line_ends[self.locate_file(last_line).0];
+        let from = first_line
+            .saturating_sub(context_lines)
+            .max(first_file_start);
+        let to = (last_line + 1 + context_lines).min(last_file_end);
+
+        let lines = (from..to)
+            .map(|line_idx| {
+                let file_start = self.locate_file(line_idx).1;
+                let text = trim_line_ending(
+                    &self.contents[self.line_offset(line_idx)..self.line_ends[line_idx]],
+                );
+                let highlight = if first_line <= line_idx && line_idx <= last_line {
+                    let range = &context.highlights[line_idx - first_line];
+                    Some(range.start.min(text.len())..range.end.min(text.len()))
+                } else {
+                    None
+                };
+                SnippetLine {
+                    line: line_idx - file_start,
+                    text,
+                    highlight,
+                }
+            })
+            .collect();
+        Some(Snippet {
+            span: context.span,
+            lines,
+        })
+    }
+
+    /// Collect the resolved span, its text, and the lines it touches.
+    ///
+    /// The span covers the bytes `start..end`. Returns `None` if the span cannot be resolved or
+    /// either end is not on a character boundary.
+    pub fn span_context<'a>(&'a self, start: usize, end: usize) -> Option<SpanContext<'a>> {
+        let span = self.resolve_offset_span(start, end)?;
+        let text = self.contents.get(start..end)?;
+
+        let (first_line, first_start) = self.locate_line(start)?;
+        // The last touched line is the one holding the last byte of the span.
+        let last_line = if end > start {
+            self.locate_line(end - 1)?.0
+        } else {
+            first_line
+        };
+
+        let mut line_ranges = Vec::with_capacity(last_line - first_line + 1);
+        let mut highlights = Vec::with_capacity(last_line - first_line + 1);
+        let mut line_start = first_start;
+        for line_idx in first_line..=last_line {
+            let line_end = self.line_ends[line_idx];
+            let hl_start = start.max(line_start) - line_start;
+            let hl_end = end.min(line_end) - line_start;
+            line_ranges.push(line_start..line_end);
+            highlights.push(hl_start..hl_end);
+            line_start = line_end;
+        }
+
+        Some(SpanContext {
+            span,
+            text,
+            lines: &self.contents[first_start..line_start],
+            line_ranges,
+            highlights,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn span_context() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "let x = 1;\nlet y = 2;\nlet z = 3;\n");
+        let ctx = sourcefile.span_context(8, 16).unwrap();
+        assert_eq!(ctx.text, "1;\nlet y");
+        assert_eq!(ctx.lines, "let x = 1;\nlet y = 2;\n");
+        assert_eq!(ctx.line_ranges, vec![0..11, 11..22]);
+        assert_eq!(ctx.highlights, vec![8..11, 0..5]);
+        assert_eq!((ctx.span.end.line, ctx.span.end.col), (1, 5));
+    }
+
+    #[test]
+    fn empty_span_context() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "abc\ndef\n");
+        let ctx = sourcefile.span_context(5, 5).unwrap();
+        assert_eq!(ctx.text, "");
+        assert_eq!(ctx.lines, "def\n");
+        assert_eq!(ctx.highlights, vec![1..1]);
+    }
+
+    #[test]
+    fn snippet() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "before\n");
+        sourcefile.add_file_raw("b", "one\ntwo\nthree\r\nfour\nfive\n");
+        // From "wo" to the end of "three", including its line ending.
+        let snippet = sourcefile.snippet(12, 22, 1).unwrap();
+        let lines: Vec<_> = snippet
+            .lines
+            .iter()
+            .map(|line| (line.line, line.text, line.highlight.clone()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (0, "one", None),
+                (1, "two", Some(1..3)),
+                (2, "three", Some(0..5)),
+                (3, "four", None),
+            ]
+        );
+
+        // Context doesn't reach into the file before.
+        let snippet = sourcefile.snippet(7, 8, 5).unwrap();
+        assert_eq!(snippet.lines.len(), 5);
+        assert_eq!(snippet.lines[0].highlight, Some(0..1));
+        assert!(sourcefile.snippet(8, 100, 1).is_none());
+    }
+}
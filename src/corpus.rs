@@ -0,0 +1,188 @@
+//! Deterministic synthetic sources with known positions, for testing and benchmarking.
+
+use {FileId, SourceFile};
+
+/// The shape of a generated corpus, see `Corpus::generate`.
+///
+/// The same config always generates the same corpus, on every platform.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CorpusConfig {
+    /// Seeds the generator. Change it to get a different corpus of the same shape.
+    pub seed: u64,
+    /// The number of files.
+    pub files: usize,
+    /// The most lines in a file. Each file gets between 0 and this many, so some may be empty.
+    pub max_lines: usize,
+    /// The most chars in a line, not counting its line ending.
+    pub max_line_chars: usize,
+    /// The percentage of line endings that are `\r\n` rather than `\n`.
+    pub crlf_percent: u8,
+    /// The percentage of chars that are multibyte, such as `é`, `中`, and `😀`.
+    pub unicode_percent: u8,
+}
+
+impl Default for CorpusConfig {
+    fn default() -> Self {
+        CorpusConfig {
+            seed: 0,
+            files: 8,
+            max_lines: 50,
+            max_line_chars: 80,
+            crlf_percent: 10,
+            unicode_percent: 5,
+        }
+    }
+}
+
+/// Where an offset should resolve to, worked out as the corpus was generated rather than by
+/// `SourceFile`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CorpusPosition {
+    /// The byte offset in the concatenated text.
+    pub offset: usize,
+    /// The file the offset is in.
+    pub file: FileId,
+    /// 0-indexed line number within the file.
+    pub line: usize,
+    /// 0-indexed column in bytes.
+    pub col: usize,
+}
+
+/// Synthetic files, and the position of every char in them.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Corpus {
+    /// The names and contents of the files, in order.
+    pub files: Vec<(String, String)>,
+    /// The position of each char boundary in the concatenated text, including line endings, in
+    /// offset order.
+    pub positions: Vec<CorpusPosition>,
+}
+
+/// Chars mixed in at `unicode_percent`, of 2, 3, and 4 bytes.
+const UNICODE_CHARS: &[char] = &['é', 'ß', 'λ', '中', '名', '•', '😀', '🦀'];
+
+impl Corpus {
+    /// Generate a corpus as described by `config`.
+    pub fn generate(config: &CorpusConfig) -> Self {
+        let mut rng = SplitMix64(config.seed);
+        let mut files = Vec::with_capacity(config.files);
+        let mut positions = Vec::new();
+        let mut offset = 0;
+        for file_idx in 0..config.files {
+            let file = FileId(file_idx);
+            let mut text = String::new();
+            let lines = rng.below(config.max_lines + 1);
+            for line in 0..lines {
+                let chars = rng.below(config.max_line_chars + 1);
+                for _ in 0..chars {
+                    let c = if rng.percent(config.unicode_percent) {
+                        UNICODE_CHARS[rng.below(UNICODE_CHARS.len())]
+                    } else {
+                        // Printable ASCII and tab.
+                        match rng.below(96) {
+                            95 => '\t',
+                            n => (b' ' + n as u8) as char,
+                        }
+                    };
+                    push_char(&mut positions, &mut text, offset, file, line, c);
+                }
+                // Leave the last line unterminated now and then.
+                if line + 1 == lines && rng.percent(20) {
+                    break;
+                }
+                if rng.percent(config.crlf_percent) {
+                    push_char(&mut positions, &mut text, offset, file, line, '\r');
+                }
+                push_char(&mut positions, &mut text, offset, file, line, '\n');
+            }
+            offset += text.len();
+            files.push((format!("corpus/{}.txt", file_idx), text));
+        }
+        Corpus { files, positions }
+    }
+
+    /// Build a `SourceFile` from the corpus's files, with the `FileId`s in `positions`.
+    pub fn source_file(&self) -> SourceFile {
+        SourceFile::from_raw_files(
+            self.files
+                .iter()
+                .map(|(name, contents)| (name, contents.as_str())),
+        )
+    }
+}
+
+/// Append `c` to `text`, the file at `file_offset`, recording where it is.
+fn push_char(
+    positions: &mut Vec<CorpusPosition>,
+    text: &mut String,
+    file_offset: usize,
+    file: FileId,
+    line: usize,
+    c: char,
+) {
+    let line_start = text.rfind('\n').map_or(0, |idx| idx + 1);
+    positions.push(CorpusPosition {
+        offset: file_offset + text.len(),
+        file,
+        line,
+        col: text.len() - line_start,
+    });
+    text.push(c);
+}
+
+/// A small, fast generator whose output is fixed for each seed, so corpora don't change between
+/// versions of a dependency.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`, or 0 if `n` is 0.
+    fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            0
+        } else {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    /// True `percent` times in 100.
+    fn percent(&mut self, percent: u8) -> bool {
+        self.below(100) < percent as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Corpus, CorpusConfig};
+
+    #[test]
+    fn corpus_positions() {
+        let config = CorpusConfig {
+            seed: 7,
+            crlf_percent: 50,
+            unicode_percent: 30,
+            ..Default::default()
+        };
+        let corpus = Corpus::generate(&config);
+        assert_eq!(corpus, Corpus::generate(&config));
+        assert_ne!(
+            corpus,
+            Corpus::generate(&CorpusConfig { seed: 8, ..config })
+        );
+
+        let sourcefile = corpus.source_file();
+        assert_eq!(corpus.positions.len(), sourcefile.contents.chars().count());
+        for expected in &corpus.positions {
+            let position = sourcefile.resolve_offset(expected.offset).unwrap();
+            assert_eq!(position.filename, sourcefile.file_name(expected.file));
+            assert_eq!((position.line, position.col), (expected.line, expected.col));
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Walking the text char by char while keeping track of the position.
+
+use {Position, SourceFile, Span};
+
+/// A cursor over `contents` that always knows its `Position`, see `SourceFile::cursor`.
+///
+/// Moving the cursor updates the position as it goes, so lexers can record positions as they
+/// scan rather than resolving offsets afterwards. The cursor moves from the end of one file to
+/// the start of the next, where the line and column start again from 0.
+#[derive(Debug, Clone)]
+pub struct SourceCursor<'a> {
+    sourcefile: &'a SourceFile,
+    /// The file the cursor is in.
+    file_idx: usize,
+    /// The offset the current file ends at.
+    file_end: usize,
+    line: usize,
+    col: usize,
+    offset: usize,
+}
+
+impl<'a> SourceCursor<'a> {
+    /// The position of the cursor.
+    ///
+    /// At the end of `contents` this is just past the last char, and so may be a line or column
+    /// that `resolve_offset` doesn't accept.
+    #[inline]
+    pub fn position(&self) -> Position<'a> {
+        Position::new(
+            &self.sourcefile.file_names[self.file_idx],
+            self.line,
+            self.col,
+            self.offset,
+        )
+    }
+
+    /// The byte offset of the cursor in `contents`.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Whether the cursor is at the end of `contents`.
+    #[inline]
+    pub fn is_at_end(&self) -> bool {
+        self.offset == self.sourcefile.contents.len()
+    }
+
+    /// The char after the cursor, without moving it.
+    #[inline]
+    pub fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// The text after the cursor, to the end of `contents`.
+    #[inline]
+    pub fn rest(&self) -> &'a str {
+        &self.sourcefile.contents[self.offset..]
+    }
+
+    /// Move past the next char, returning it.
+    pub fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.col = 0;
+        } else {
+            self.col += c.len_utf8();
+        }
+        if self.offset == self.file_end && !self.is_at_end() {
+            let sourcefile = self.sourcefile;
+            let (line_idx, _) = sourcefile
+                .locate_line(self.offset)
+                .expect("offset before the end is on a line");
+            self.file_idx = sourcefile.locate_file(line_idx).0;
+            self.file_end = sourcefile.line_offset(sourcefile.file_line_ends[self.file_idx]);
+            self.line = 0;
+            self.col = 0;
+        }
+        Some(c)
+    }
+
+    /// Move past up to `n` chars, returning how many were moved past.
+    pub fn advance_by(&mut self, n: usize) -> usize {
+        let mut moved = 0;
+        while moved < n && self.bump().is_some() {
+            moved += 1;
+        }
+        moved
+    }
+
+    /// Move past chars while `predicate` returns true, returning the text moved past.
+    ///
+    /// This doesn't cross into the next file, so the text is always from one file.
+    pub fn advance_while<F>(&mut self, mut predicate: F) -> &'a str
+    where
+        F: FnMut(char) -> bool,
+    {
+        let start = self.offset;
+        let file_end = self.file_end;
+        while self.offset < file_end {
+            match self.peek() {
+                Some(c) if predicate(c) => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+        &self.sourcefile.contents[start..self.offset]
+    }
+
+    /// The span from an earlier position of the cursor, such as the start of a token, to where
+    /// it is now.
+    pub fn span_from(&self, checkpoint: Position<'a>) -> Span<'a> {
+        Span {
+            start: checkpoint,
+            end: self.position(),
+        }
+    }
+}
+
+impl SourceFile {
+    /// A cursor at the start of `contents`, or `None` if there is no text.
+    pub fn cursor<'a>(&'a self) -> Option<SourceCursor<'a>> {
+        let (line_idx, _) = self.locate_line(0)?;
+        let file_idx = self.locate_file(line_idx).0;
+        Some(SourceCursor {
+            sourcefile: self,
+            file_idx,
+            file_end: self.line_offset(self.file_line_ends[file_idx]),
+            line: 0,
+            col: 0,
+            offset: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn cursor() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("empty", "");
+        sourcefile.add_file_raw("a", "é1\nx");
+        sourcefile.add_file_raw("b", "yz\n");
+
+        let mut cursor = sourcefile.cursor().unwrap();
+        let mut offset = 0;
+        while !cursor.is_at_end() {
+            assert_eq!(
+                cursor.position(),
+                sourcefile.resolve_offset(offset).unwrap()
+            );
+            offset += cursor.bump().unwrap().len_utf8();
+        }
+        let end = cursor.position();
+        assert_eq!(
+            (end.filename, end.line, end.col, end.offset),
+            ("b", 1, 0, 8)
+        );
+        assert_eq!(cursor.bump(), None);
+
+        let mut cursor = sourcefile.cursor().unwrap();
+        assert_eq!(cursor.advance_by(2), 2);
+        assert_eq!(cursor.peek(), Some('\n'));
+        cursor.bump();
+        let start = cursor.position();
+        // Stops at the end of "a" even though "y" matches.
+        assert_eq!(cursor.advance_while(char::is_alphabetic), "x");
+        let span = cursor.span_from(start);
+        assert_eq!(
+            (span.start.line, span.end.filename, span.end.line),
+            (1, "b", 0)
+        );
+        assert_eq!(cursor.rest(), "yz\n");
+        assert_eq!(cursor.advance_by(10), 3);
+
+        assert!(SourceFile::new().cursor().is_none());
+    }
+}
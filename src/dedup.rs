@@ -0,0 +1,130 @@
+//! Finding files that were added more than once.
+
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::path::PathBuf;
+
+use {FileId, SourceFile};
+
+/// Files that look like double inclusions, see `SourceFile::canonical_dedup_report`.
+///
+/// Each group has at least two files, in the order they were added, and groups are ordered by
+/// their first file.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct DedupReport {
+    /// Files added under the same name.
+    pub duplicate_names: Vec<Vec<FileId>>,
+    /// Files with the same (non-empty) contents, under at least two different names.
+    pub identical_contents: Vec<Vec<FileId>>,
+    /// Files read from disk whose paths resolve to the same file, with that file's canonical
+    /// path.
+    pub same_canonical_path: Vec<(PathBuf, Vec<FileId>)>,
+}
+
+impl DedupReport {
+    /// Whether nothing was found.
+    pub fn is_empty(&self) -> bool {
+        self.duplicate_names.is_empty()
+            && self.identical_contents.is_empty()
+            && self.same_canonical_path.is_empty()
+    }
+}
+
+/// Group items by key, keeping only groups of two or more.
+fn groups<K, I>(items: I) -> Vec<(K, Vec<FileId>)>
+where
+    K: Eq + Hash + Clone,
+    I: IntoIterator<Item = (K, FileId)>,
+{
+    let mut index = HashMap::new();
+    let mut groups: Vec<(K, Vec<FileId>)> = Vec::new();
+    for (key, id) in items {
+        let idx = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, Vec::new()));
+            groups.len() - 1
+        });
+        groups[idx].1.push(id);
+    }
+    groups.retain(|(_, ids)| ids.len() > 1);
+    groups
+}
+
+impl SourceFile {
+    /// Look for files included more than once: by name, by contents, or by resolving to the same
+    /// file on disk (e.g. through a symlink or `..`).
+    ///
+    /// Only files added with `add_file` have paths to compare. Paths that can no longer be
+    /// resolved, e.g. because the file was deleted, are skipped.
+    pub fn canonical_dedup_report(&self) -> DedupReport {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("canonical_dedup_report", files = self.file_names.len()).entered();
+        let duplicate_names = groups(self.files().map(|file| (file.name, file.id)))
+            .into_iter()
+            .map(|(_, ids)| ids)
+            .collect();
+
+        let identical_contents = groups(
+            self.files()
+                .filter(|file| !file.contents.is_empty())
+                .map(|file| (file.contents, file.id)),
+        )
+        .into_iter()
+        .map(|(_, ids)| ids)
+        .filter(|ids| {
+            let first = self.file_name(ids[0]);
+            ids.iter().any(|&id| self.file_name(id) != first)
+        })
+        .collect();
+
+        let same_canonical_path = groups(self.file_paths.iter().enumerate().filter_map(
+            |(idx, path)| {
+                let (absolute, _) = path.as_ref()?;
+                Some((fs::canonicalize(absolute).ok()?, FileId(idx)))
+            },
+        ));
+
+        DedupReport {
+            duplicate_names,
+            identical_contents,
+            same_canonical_path,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use std::fs;
+
+    use SourceFile;
+
+    #[test]
+    fn dedup_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "text\n").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file(&path).unwrap();
+        let b = sourcefile
+            .add_file(dir.path().join("sub").join("..").join("a.txt"))
+            .unwrap();
+        let c = sourcefile.add_file_raw("c", "other\n");
+        let d = sourcefile.add_file_raw("c", "other\n");
+        sourcefile.add_file_raw("e", "");
+        sourcefile.add_file_raw("f", "");
+
+        let report = sourcefile.canonical_dedup_report();
+        assert_eq!(report.duplicate_names, [vec![c, d]]);
+        assert_eq!(report.identical_contents, [vec![a, b]]);
+        assert_eq!(
+            report.same_canonical_path,
+            [(fs::canonicalize(&path).unwrap(), vec![a, b])]
+        );
+        assert!(SourceFile::new().canonical_dedup_report().is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+//! Remapping offsets across edits to a text.
+
+use std::ops::Range;
+
+/// How offsets into a text move when parts of it are replaced.
+///
+/// This is for tools that transform text themselves (formatters, fix-it applicators) but still
+/// want spans into the old text to point at the same places in the new one. Build a map from the
+/// edits of one transformation with `from_edits`, and chain the maps of later transformations on
+/// with `then`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct DeltaMap {
+    /// The edits of each transformation in turn. In each, edits are sorted and given as the
+    /// replaced range in that transformation's input, and the end of the replacement in its
+    /// output.
+    layers: Vec<Vec<(Range<usize>, usize)>>,
+}
+
+impl DeltaMap {
+    /// A map that leaves every offset unchanged.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Build a map from `(replaced range, replacement length)` edits, all given as ranges of the
+    /// text before any of them are made.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if a range is reversed or two ranges overlap. Insertions (empty
+    /// ranges) may touch other edits.
+    pub fn from_edits<I>(edits: I) -> Self
+    where
+        I: IntoIterator<Item = (Range<usize>, usize)>,
+    {
+        let mut edits: Vec<(Range<usize>, usize)> = edits.into_iter().collect();
+        edits.sort_by_key(|(range, _)| (range.start, range.end));
+        let mut layer = Vec::with_capacity(edits.len());
+        let mut prev_end = 0;
+        let mut shift = 0isize;
+        for (range, new_len) in edits {
+            assert!(range.start <= range.end, "reversed edit range {:?}", range);
+            assert!(range.start >= prev_end, "overlapping edit at {:?}", range);
+            prev_end = range.end;
+            let new_end = (range.start as isize + shift) as usize + new_len;
+            shift += new_len as isize - range.len() as isize;
+            layer.push((range, new_end));
+        }
+        let layers = if layer.is_empty() {
+            Vec::new()
+        } else {
+            vec![layer]
+        };
+        DeltaMap { layers }
+    }
+
+    /// Chain `next`, a map of edits made to the output of `self`, onto this one.
+    pub fn then(mut self, next: DeltaMap) -> Self {
+        self.layers.extend(next.layers);
+        self
+    }
+
+    /// Translate an offset into the original text to the edited text.
+    ///
+    /// Returns `None` if the offset was inside replaced text. An offset at the start of an edit
+    /// maps to the start of the replacement, and one at its end to the end of the replacement.
+    /// In particular, an offset where text was inserted maps to just before the inserted text.
+    pub fn map_offset(&self, offset: usize) -> Option<usize> {
+        self.layers
+            .iter()
+            .try_fold(offset, |offset, layer| map_in_layer(layer, offset))
+    }
+
+    /// Translate both ends of a span, returning `None` if either was inside replaced text.
+    pub fn map_span(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        Some((self.map_offset(start)?, self.map_offset(end)?))
+    }
+}
+
+fn map_in_layer(layer: &[(Range<usize>, usize)], offset: usize) -> Option<usize> {
+    // The edits starting before `offset`; the last one is the only one it can be inside.
+    let idx = layer.partition_point(|(range, _)| range.start < offset);
+    match idx.checked_sub(1).map(|idx| &layer[idx]) {
+        None => Some(offset),
+        Some((range, _)) if offset < range.end => None,
+        Some((range, new_end)) => Some(new_end + (offset - range.end)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeltaMap;
+
+    #[test]
+    fn delta_map() {
+        // "let x = 1;" -> "let value = 10;"
+        let first = DeltaMap::from_edits(vec![(8..9, 2), (4..5, 5)]);
+        assert_eq!(first.map_offset(0), Some(0));
+        assert_eq!(first.map_offset(4), Some(4));
+        assert_eq!(first.map_offset(5), Some(9));
+        assert_eq!(first.map_offset(8), Some(12));
+        assert_eq!(first.map_offset(9), Some(14));
+        assert_eq!(first.map_offset(10), Some(15));
+        assert_eq!(first.map_offset(20), Some(25));
+
+        // "let value = 10;" -> "let mut value = 10;"
+        let second = DeltaMap::from_edits(vec![(4..4, 4)]);
+        assert_eq!(second.map_offset(4), Some(4));
+        assert_eq!(second.map_offset(5), Some(9));
+
+        let both = first.then(second);
+        assert_eq!(both.map_span(9, 10), Some((18, 19)));
+        assert_eq!(both.map_offset(0), Some(0));
+        assert_eq!(DeltaMap::new().map_offset(3), Some(3));
+
+        let shrink = DeltaMap::from_edits(vec![(2..8, 0)]);
+        assert_eq!(shrink.map_offset(5), None);
+        assert_eq!(shrink.map_span(1, 8), Some((1, 2)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn overlapping_edits() {
+        DeltaMap::from_edits(vec![(0..4, 1), (3..5, 1)]);
+    }
+}
@@ -0,0 +1,545 @@
+//! Non-panicking `try_*` variants of the lookup methods, sharing one error type.
+//!
+//! Methods taking a `FileId` panic if it wasn't returned by the `SourceFile` they are called on
+//! (or was returned before `clear`). `contains_file` checks for this up front, and the `try_*`
+//! methods here report it as `Error::UnknownFile` instead. `LineTables::try_lines_of_str` does
+//! the same for the line table cache.
+//!
+//! Methods that panic only for an argument no input can produce have no `try_*` variant, since
+//! the panic is a bug in the caller, as for `slice::chunks`:
+//!
+//!  - `chunks_by_lines` panics for a chunk size of 0, `wrap_line`, `try_wrap_line` and
+//!    `WrappedLine::new` for a width of 0, and `set_tab_width` and
+//!    `ColumnEncoding::column_with_tabs` for a tab width of 0.
+//!  - `ColumnEncoding::column` and `column_with_tabs` panic for a byte that isn't in `line` or
+//!    not on a char boundary. `try_resolve_offset_with_encoding` checks offsets into `contents`.
+//!  - `SpanSideTable::span`, `get` and `get_mut` panic for a `SpanId` from another table, and
+//!    `SpanTree`'s accessors for an index out of bounds, as slices do.
+//!  - `SpanSideTable::insert`, `SpanTree::new` and `DeltaMap::from_edits` panic for reversed
+//!    spans, and `DeltaMap::from_edits` for overlapping ones too.
+
+use std::ops::Range;
+use std::path::Path;
+use std::{error, fmt};
+
+use revision::Stale;
+use {
+    ColumnEncoding, FileId, FileLines, FileStyle, FoldedView, Outline, Position, PositionId,
+    SanitizedLine, SourceFile, Span, TaggedSpan, WrappedLine,
+};
+
+/// The reasons a lookup on a `SourceFile` can fail.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum Error {
+    /// The offset is not the offset of a byte in `contents`.
+    OffsetOutOfBounds {
+        /// The offset that was looked up.
+        offset: usize,
+        /// The length of `contents`.
+        len: usize,
+    },
+    /// The offset is inside `contents`, but in the middle of a character.
+    NotCharBoundary {
+        /// The offset that was looked up.
+        offset: usize,
+    },
+    /// The end of a span is before its start.
+    ReversedSpan {
+        /// The start of the span.
+        start: usize,
+        /// The end of the span.
+        end: usize,
+    },
+    /// The `FileId` was not returned by this `SourceFile`.
+    UnknownFile(FileId),
+    /// The file has no line with this index.
+    LineOutOfBounds {
+        /// The file that was looked up.
+        file: FileId,
+        /// The 0-indexed line that was looked up.
+        line: usize,
+        /// The number of lines in the file.
+        line_count: usize,
+    },
+    /// A `TaggedSpan` was resolved against a later revision.
+    Stale(Stale),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::OffsetOutOfBounds { offset, len } => write!(
+                f,
+                "offset {} is out of bounds for contents of length {}",
+                offset, len
+            ),
+            Error::NotCharBoundary { offset } => {
+                write!(f, "offset {} is not on a character boundary", offset)
+            }
+            Error::ReversedSpan { start, end } => {
+                write!(f, "span end {} is before its start {}", end, start)
+            }
+            Error::UnknownFile(id) => write!(f, "unknown file id {}", id.0),
+            Error::LineOutOfBounds {
+                file,
+                line,
+                line_count,
+            } => write!(
+                f,
+                "line {} is out of bounds for file {} with {} lines",
+                line, file.0, line_count
+            ),
+            Error::Stale(ref stale) => stale.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Stale(ref stale) => Some(stale),
+            _ => None,
+        }
+    }
+}
+
+impl From<Stale> for Error {
+    fn from(stale: Stale) -> Self {
+        Error::Stale(stale)
+    }
+}
+
+/// The reasons `SourceFile::try_add_file_raw` can refuse a file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum AddError {
+    /// The file has no contents, so no offset could ever resolve into it.
+    Empty,
+}
+
+impl fmt::Display for AddError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AddError::Empty => f.write_str("file is empty"),
+        }
+    }
+}
+
+impl error::Error for AddError {}
+
+impl SourceFile {
+    /// Whether `id` refers to one of the files in this `SourceFile`.
+    #[inline]
+    pub fn contains_file(&self, id: FileId) -> bool {
+        id.0 < self.file_names.len()
+    }
+
+    fn check_file(&self, id: FileId) -> Result<(), Error> {
+        if self.contains_file(id) {
+            Ok(())
+        } else {
+            Err(Error::UnknownFile(id))
+        }
+    }
+
+    fn check_offset(&self, offset: usize) -> Result<(), Error> {
+        if offset >= self.contents.len() {
+            Err(Error::OffsetOutOfBounds {
+                offset,
+                len: self.contents.len(),
+            })
+        } else if !self.contents.is_char_boundary(offset) {
+            Err(Error::NotCharBoundary { offset })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Like `resolve_offset`, but saying why the offset couldn't be resolved.
+    ///
+    /// Unlike `resolve_offset`, an offset in the middle of a character is an error.
+    pub fn try_resolve_offset<'a>(&'a self, offset: usize) -> Result<Position<'a>, Error> {
+        self.check_offset(offset)?;
+        // Every in-bounds offset is on some line, since the lines cover `contents`.
+        Ok(self
+            .resolve_offset(offset)
+            .expect("in-bounds offset is on a line"))
+    }
+
+    /// Like `resolve_offset_span`, but saying why the span couldn't be resolved.
+    pub fn try_resolve_offset_span<'a>(
+        &'a self,
+        start: usize,
+        end: usize,
+    ) -> Result<Span<'a>, Error> {
+        if end < start {
+            return Err(Error::ReversedSpan { start, end });
+        }
+        Ok(Span {
+            start: self.try_resolve_offset(start)?,
+            end: self.try_resolve_offset(end)?,
+        })
+    }
+
+    /// Like `resolve_tagged_span`, with staleness and bad offsets reported as one error type.
+    pub fn try_resolve_tagged_span<'a>(&'a self, span: TaggedSpan) -> Result<Span<'a>, Error> {
+        self.resolve_tagged_span(span)?;
+        self.try_resolve_offset_span(span.start, span.end)
+    }
+
+    /// Like `file_name`, but returning an error rather than panicking for an unknown file.
+    pub fn try_file_name(&self, id: FileId) -> Result<&str, Error> {
+        self.check_file(id)?;
+        Ok(self.file_name(id))
+    }
+
+    /// Like `line_count`, but returning an error rather than panicking for an unknown file.
+    pub fn try_line_count(&self, id: FileId) -> Result<usize, Error> {
+        self.check_file(id)?;
+        Ok(self.line_count(id))
+    }
+
+    /// Like `file_path`, but returning an error rather than panicking for an unknown file.
+    pub fn try_file_path(&self, id: FileId) -> Result<Option<&Path>, Error> {
+        self.check_file(id)?;
+        Ok(self.file_path(id))
+    }
+
+    /// Like `file_language`, but returning an error rather than panicking for an unknown file.
+    pub fn try_file_language(&self, id: FileId) -> Result<Option<&str>, Error> {
+        self.check_file(id)?;
+        Ok(self.file_language(id))
+    }
+
+    /// Like `set_file_language`, but returning an error rather than panicking for an unknown
+    /// file.
+    pub fn try_set_file_language(
+        &mut self,
+        id: FileId,
+        language: impl Into<String>,
+    ) -> Result<(), Error> {
+        self.check_file(id)?;
+        self.set_file_language(id, language);
+        Ok(())
+    }
+
+    /// Like `line_char_len`, but saying whether the file or the line was missing.
+    pub fn try_line_char_len(&self, id: FileId, line: usize) -> Result<usize, Error> {
+        self.check_file(id)?;
+        self.line_char_len(id, line)
+            .ok_or_else(|| self.line_out_of_bounds(id, line))
+    }
+
+    /// Like `file_span`, but returning an error rather than panicking for an unknown file.
+    pub fn try_file_span(&self, id: FileId) -> Result<Range<usize>, Error> {
+        self.check_file(id)?;
+        Ok(self.file_span(id))
+    }
+
+    /// Like `file_contents`, but returning an error rather than panicking for an unknown file.
+    pub fn try_file_contents(&self, id: FileId) -> Result<&str, Error> {
+        self.check_file(id)?;
+        Ok(self.file_contents(id))
+    }
+
+    /// Like `line_start`, but saying whether the file or the line was missing.
+    pub fn try_line_start(&self, id: FileId, line: usize) -> Result<usize, Error> {
+        self.check_file(id)?;
+        self.line_start(id, line)
+            .ok_or_else(|| self.line_out_of_bounds(id, line))
+    }
+
+    /// Like `line`, but saying whether the file or the line was missing.
+    pub fn try_line(&self, id: FileId, line: usize) -> Result<&str, Error> {
+        self.check_file(id)?;
+        self.line(id, line)
+            .ok_or_else(|| self.line_out_of_bounds(id, line))
+    }
+
+    /// Like `lines`, but returning an error rather than panicking for an unknown file.
+    pub fn try_lines<'a>(&'a self, id: FileId) -> Result<FileLines<'a>, Error> {
+        self.check_file(id)?;
+        Ok(self.lines(id))
+    }
+
+    /// Like `line_table`, but returning an error rather than panicking for an unknown file.
+    pub fn try_line_table(&self, id: FileId) -> Result<Vec<&str>, Error> {
+        self.check_file(id)?;
+        Ok(self.line_table(id))
+    }
+
+    /// Like `line_annotation`, but returning an error rather than panicking for an unknown file.
+    ///
+    /// A line without an annotation is still `Ok(None)`, whether or not the file has that line.
+    pub fn try_line_annotation(&self, id: FileId, line: usize) -> Result<Option<&str>, Error> {
+        self.check_file(id)?;
+        Ok(self.line_annotation(id, line))
+    }
+
+    /// Like `set_line_annotations`, but returning an error rather than panicking for an unknown
+    /// file.
+    pub fn try_set_line_annotations<I, S>(
+        &mut self,
+        id: FileId,
+        annotations: I,
+    ) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.check_file(id)?;
+        self.set_line_annotations(id, annotations);
+        Ok(())
+    }
+
+    /// Like `provenance`, but returning an error rather than panicking for an unknown file.
+    pub fn try_provenance(&self, id: FileId) -> Result<Option<(usize, usize)>, Error> {
+        self.check_file(id)?;
+        Ok(self.provenance(id))
+    }
+
+    /// Like `replaced_ranges`, but returning an error rather than panicking for an unknown file.
+    pub fn try_replaced_ranges(&self, id: FileId) -> Result<&[(usize, usize)], Error> {
+        self.check_file(id)?;
+        Ok(self.replaced_ranges(id))
+    }
+
+    /// Like `annotate_file`, but returning an error rather than panicking for an unknown file.
+    ///
+    /// Spans that don't fit the file still give `Ok(None)`, as for `annotate_file`.
+    pub fn try_annotate_file<F, S>(
+        &self,
+        id: FileId,
+        spans: &[(usize, usize)],
+        markers: F,
+    ) -> Result<Option<String>, Error>
+    where
+        F: FnMut(usize) -> (S, S),
+        S: AsRef<str>,
+    {
+        self.check_file(id)?;
+        Ok(self.annotate_file(id, spans, markers))
+    }
+
+    /// Like `fold_file`, but returning an error rather than panicking for an unknown file.
+    pub fn try_fold_file(
+        &self,
+        id: FileId,
+        folds: &[Range<usize>],
+        placeholder: &str,
+    ) -> Result<FoldedView, Error> {
+        self.check_file(id)?;
+        Ok(self.fold_file(id, folds, placeholder))
+    }
+
+    /// Like `outline`, but returning an error rather than panicking for an unknown file.
+    pub fn try_outline(&self, id: FileId) -> Result<Outline, Error> {
+        self.check_file(id)?;
+        Ok(self.outline(id))
+    }
+
+    /// Like `file_info`, but returning an error rather than panicking for an unknown file.
+    pub fn try_file_info(&self, id: FileId) -> Result<FileStyle, Error> {
+        self.check_file(id)?;
+        Ok(self.file_info(id))
+    }
+
+    /// Like `sanitized_line`, but saying whether the file or the line was missing.
+    pub fn try_sanitized_line(&self, id: FileId, line: usize) -> Result<SanitizedLine, Error> {
+        self.check_file(id)?;
+        self.sanitized_line(id, line)
+            .ok_or_else(|| self.line_out_of_bounds(id, line))
+    }
+
+    /// Like `wrap_line`, but saying whether the file or the line was missing.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `width` is 0.
+    pub fn try_wrap_line<'a>(
+        &'a self,
+        id: FileId,
+        line: usize,
+        width: usize,
+    ) -> Result<WrappedLine<'a>, Error> {
+        self.check_file(id)?;
+        self.wrap_line(id, line, width)
+            .ok_or_else(|| self.line_out_of_bounds(id, line))
+    }
+
+    /// Like `snippet_option`, but returning an error rather than panicking for an unknown file.
+    pub fn try_snippet_option(&self, id: FileId) -> Result<Option<&str>, Error> {
+        self.check_file(id)?;
+        Ok(self.snippet_option(id))
+    }
+
+    /// Like `position`, but returning an error rather than panicking for an unknown file.
+    pub fn try_position<'a>(&'a self, id: PositionId) -> Result<Position<'a>, Error> {
+        self.check_file(id.file)?;
+        Ok(self.position(id))
+    }
+
+    /// Like `resolve_offset_with_encoding`, but saying why the offset couldn't be resolved.
+    pub fn try_resolve_offset_with_encoding<'a>(
+        &'a self,
+        offset: usize,
+        encoding: ColumnEncoding,
+    ) -> Result<Position<'a>, Error> {
+        self.check_offset(offset)?;
+        // A checked offset is on a line and a char boundary.
+        Ok(self
+            .resolve_offset_with_encoding(offset, encoding)
+            .expect("in-bounds offset on a char boundary has a column"))
+    }
+
+    fn line_out_of_bounds(&self, id: FileId, line: usize) -> Error {
+        Error::LineOutOfBounds {
+            file: id,
+            line,
+            line_count: self.line_count(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use {ColumnEncoding, FileId, PositionId, SourceFile};
+
+    #[test]
+    fn try_variants() {
+        let mut sourcefile = SourceFile::new();
+        let id = sourcefile.add_file_raw("a", "é\nb\n");
+        let unknown = FileId(1);
+        assert!(sourcefile.contains_file(id));
+        assert!(!sourcefile.contains_file(unknown));
+
+        assert_eq!(sourcefile.try_resolve_offset(3).unwrap().line, 1);
+        assert_eq!(
+            sourcefile.try_resolve_offset(1),
+            Err(Error::NotCharBoundary { offset: 1 })
+        );
+        assert_eq!(
+            sourcefile.try_resolve_offset(5),
+            Err(Error::OffsetOutOfBounds { offset: 5, len: 5 })
+        );
+        assert_eq!(
+            sourcefile.try_resolve_offset_span(3, 0),
+            Err(Error::ReversedSpan { start: 3, end: 0 })
+        );
+
+        assert_eq!(sourcefile.try_file_name(id), Ok("a"));
+        assert_eq!(
+            sourcefile.try_file_name(unknown),
+            Err(Error::UnknownFile(unknown))
+        );
+        assert_eq!(sourcefile.try_line_char_len(id, 0), Ok(2));
+        assert_eq!(
+            sourcefile.try_line_char_len(id, 2),
+            Err(Error::LineOutOfBounds {
+                file: id,
+                line: 2,
+                line_count: 2
+            })
+        );
+        assert!(sourcefile.try_set_file_language(unknown, "rs").is_err());
+
+        assert_eq!(sourcefile.try_file_span(id), Ok(0..5));
+        assert_eq!(sourcefile.try_file_contents(id), Ok("é\nb\n"));
+        assert_eq!(sourcefile.try_line_start(id, 1), Ok(3));
+        assert_eq!(sourcefile.try_line(id, 1), Ok("b"));
+        assert_eq!(
+            sourcefile.try_line(id, 2),
+            Err(Error::LineOutOfBounds {
+                file: id,
+                line: 2,
+                line_count: 2
+            })
+        );
+        assert_eq!(sourcefile.try_lines(id).unwrap().count(), 2);
+        assert_eq!(sourcefile.try_line_table(id), Ok(vec!["é", "b"]));
+        assert_eq!(sourcefile.try_provenance(id), Ok(None));
+        assert_eq!(sourcefile.try_replaced_ranges(id), Ok(&[][..]));
+        assert!(sourcefile.try_set_line_annotations(id, vec!["c1"]).is_ok());
+        assert_eq!(sourcefile.try_line_annotation(id, 0), Ok(Some("c1")));
+        assert_eq!(sourcefile.try_line_annotation(id, 1), Ok(None));
+        assert_eq!(
+            sourcefile.try_annotate_file(id, &[(3, 4)], |_| ("[", "]")),
+            Ok(Some("é\n[b]\n".to_string()))
+        );
+        assert!(sourcefile.try_fold_file(id, &[0..1, 0..2], "...").is_ok());
+        for result in [
+            sourcefile.try_file_span(unknown).map(|_| ()),
+            sourcefile.try_file_contents(unknown).map(|_| ()),
+            sourcefile.try_line_start(unknown, 0).map(|_| ()),
+            sourcefile.try_line(unknown, 0).map(|_| ()),
+            sourcefile.try_lines(unknown).map(|_| ()),
+            sourcefile.try_line_table(unknown).map(|_| ()),
+            sourcefile.try_line_annotation(unknown, 0).map(|_| ()),
+            sourcefile.try_provenance(unknown).map(|_| ()),
+            sourcefile.try_replaced_ranges(unknown).map(|_| ()),
+            sourcefile
+                .try_annotate_file(unknown, &[], |_| ("", ""))
+                .map(|_| ()),
+            sourcefile.try_fold_file(unknown, &[], "...").map(|_| ()),
+        ] {
+            assert_eq!(result, Err(Error::UnknownFile(unknown)));
+        }
+        assert_eq!(sourcefile.try_outline(id).unwrap().regions().len(), 0);
+        assert!(sourcefile.try_file_info(id).is_ok());
+        assert_eq!(sourcefile.try_sanitized_line(id, 1).unwrap().text, "b");
+        assert!(sourcefile.try_sanitized_line(id, 2).is_err());
+        assert_eq!(
+            sourcefile.try_wrap_line(id, 1, 4).unwrap().rows().count(),
+            1
+        );
+        assert!(sourcefile.try_wrap_line(id, 2, 4).is_err());
+        assert_eq!(sourcefile.try_snippet_option(id), Ok(None));
+        let position = sourcefile.resolve_offset_id(3).unwrap();
+        assert_eq!(sourcefile.try_position(position).unwrap().line, 1);
+        assert_eq!(
+            sourcefile
+                .try_resolve_offset_with_encoding(2, ColumnEncoding::Chars)
+                .unwrap()
+                .col,
+            1
+        );
+        assert_eq!(
+            sourcefile.try_resolve_offset_with_encoding(1, ColumnEncoding::Chars),
+            Err(Error::NotCharBoundary { offset: 1 })
+        );
+        let tables = sourcefile.line_tables();
+        assert_eq!(tables.try_lines_of_str(id), Ok(&["é", "b"][..]));
+        assert_eq!(
+            tables.try_lines_of_str(unknown),
+            Err(Error::UnknownFile(unknown))
+        );
+        for result in [
+            sourcefile.try_outline(unknown).map(|_| ()),
+            sourcefile.try_file_info(unknown).map(|_| ()),
+            sourcefile.try_sanitized_line(unknown, 0).map(|_| ()),
+            sourcefile.try_wrap_line(unknown, 0, 4).map(|_| ()),
+            sourcefile.try_snippet_option(unknown).map(|_| ()),
+            sourcefile
+                .try_position(PositionId {
+                    file: unknown,
+                    ..position
+                })
+                .map(|_| ()),
+        ] {
+            assert_eq!(result, Err(Error::UnknownFile(unknown)));
+        }
+        assert_eq!(
+            sourcefile.try_set_line_annotations(unknown, vec!["c1"]),
+            Err(Error::UnknownFile(unknown))
+        );
+
+        let span = sourcefile.tag_span(0, 3);
+        sourcefile.clear();
+        match sourcefile.try_resolve_tagged_span(span) {
+            Err(Error::Stale(_)) => (),
+            other => panic!("expected a stale span, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Positions as reported by external tools such as gcc, clang and tsc.
+
+use std::fmt;
+use std::path::Path;
+
+use {ColumnEncoding, FileId, SourceFile};
+
+/// A 1-based line and column in a file named by path, as printed by compilers.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ExternalPosition {
+    /// The path of the file as the tool printed it.
+    pub path: String,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number.
+    pub col: usize,
+}
+
+impl ExternalPosition {
+    /// Parse a position at the start of a line of tool output, ignoring the message after it.
+    ///
+    /// Both `path:line:col` (gcc, clang, rustc) and `path(line,col)` (tsc, msbuild) are
+    /// accepted. A missing column, as in `path:line`, is taken to be 1.
+    pub fn parse(text: &str) -> Option<ExternalPosition> {
+        ExternalPosition::parse_colons(text).or_else(|| ExternalPosition::parse_parens(text))
+    }
+
+    fn parse_parens(text: &str) -> Option<ExternalPosition> {
+        let open = text.find('(')?;
+        let close = open + text[open..].find(')')?;
+        let mut numbers = text[open + 1..close].split(',');
+        let line = numbers.next()?.trim().parse().ok()?;
+        let col = numbers.next()?.trim().parse().ok()?;
+        if numbers.next().is_some() {
+            return None;
+        }
+        ExternalPosition::new(&text[..open], line, col)
+    }
+
+    fn parse_colons(text: &str) -> Option<ExternalPosition> {
+        // The path may itself contain colons, as in `C:\src\main.c`, so look for the first colon
+        // followed by a number.
+        for (colon, _) in text.match_indices(':').filter(|&(colon, _)| colon > 0) {
+            let rest = &text[colon + 1..];
+            let (line, rest) = match leading_number(rest) {
+                Some(found) => found,
+                None => continue,
+            };
+            if !(rest.is_empty() || rest.starts_with(':') || rest.starts_with(' ')) {
+                continue;
+            }
+            let col = rest
+                .strip_prefix(':')
+                .and_then(leading_number)
+                .map_or(1, |(col, _)| col);
+            return ExternalPosition::new(&text[..colon], line, col);
+        }
+        None
+    }
+
+    fn new(path: &str, line: usize, col: usize) -> Option<ExternalPosition> {
+        if path.is_empty() || line == 0 || col == 0 {
+            return None;
+        }
+        Some(ExternalPosition {
+            path: path.to_owned(),
+            line,
+            col,
+        })
+    }
+}
+
+impl fmt::Display for ExternalPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.path, self.line, self.col)
+    }
+}
+
+/// Split a run of ASCII digits off the start of `text`, if there is one.
+fn leading_number(text: &str) -> Option<(usize, &str)> {
+    let len = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    Some((text[..len].parse().ok()?, &text[len..]))
+}
+
+/// The components of a path separated by `/` or `\`, ignoring `.` and empty components.
+fn components(path: &str) -> Vec<&str> {
+    path.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect()
+}
+
+impl SourceFile {
+    /// Find the file an external tool means by `path`.
+    ///
+    /// Tools often print paths relative to a different directory, so this tries, in order: a
+    /// file added under exactly `path`, a file read from disk at `path`, and then the file whose
+    /// name or path shares the most trailing components with `path`. The last must at least
+    /// agree on the file name, and is `None` if several files match equally well.
+    pub fn find_file(&self, path: &str) -> Option<FileId> {
+        if let Some(id) = self.file_by_name(path) {
+            return Some(id);
+        }
+        let as_path = Path::new(path);
+        if let Some(idx) = self.file_paths.iter().position(|file_path| {
+            file_path
+                .as_ref()
+                .is_some_and(|(absolute, _)| absolute == as_path)
+        }) {
+            return Some(FileId(idx));
+        }
+
+        let wanted = components(path);
+        let shared = |candidate: &str| {
+            components(candidate)
+                .iter()
+                .rev()
+                .zip(wanted.iter().rev())
+                .take_while(|&(a, b)| a == b)
+                .count()
+        };
+        let mut best = None;
+        let mut best_shared = 0;
+        let mut tied = false;
+        for (idx, name) in self.file_names.iter().enumerate() {
+            let mut score = shared(name);
+            if let Some((ref absolute, _)) = self.file_paths[idx] {
+                if let Some(absolute) = absolute.to_str() {
+                    score = score.max(shared(absolute));
+                }
+            }
+            if score > best_shared {
+                best = Some(FileId(idx));
+                best_shared = score;
+                tied = false;
+            } else if score == best_shared && score > 0 {
+                tied = true;
+            }
+        }
+        if tied {
+            None
+        } else {
+            best
+        }
+    }
+
+    /// The byte offset in `contents` of a position reported by an external tool.
+    ///
+    /// The file is found with `find_file`. As with `offset_of`, the column may point just past
+    /// the end of the line's text but not into its line ending.
+    pub fn from_external(
+        &self,
+        position: &ExternalPosition,
+        encoding: ColumnEncoding,
+    ) -> Option<usize> {
+        let id = self.find_file(&position.path)?;
+        let line = position.line.checked_sub(1)?;
+        let col = position.col.checked_sub(1)?;
+        let col = encoding.byte(self.line(id, line)?, col)?;
+        self.offset_of_line_col(id, line, col)
+    }
+
+    /// The position of a byte offset in `contents` as an external tool would report it, with the
+    /// name the file was added under as its path.
+    ///
+    /// Returns `None` if the offset is past the end of `contents` or in the middle of a char, as
+    /// `resolve_offset_with_encoding` does.
+    pub fn to_external(&self, offset: usize, encoding: ColumnEncoding) -> Option<ExternalPosition> {
+        let position = self.resolve_offset_with_encoding(offset, encoding)?;
+        Some(ExternalPosition {
+            path: position.filename.to_owned(),
+            line: position.line + 1,
+            col: position.col + 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExternalPosition;
+    use {ColumnEncoding, SourceFile};
+
+    #[test]
+    fn parse() {
+        let gcc = ExternalPosition::parse("src/main.c:10:5: error: expected ';'").unwrap();
+        assert_eq!(
+            (gcc.path.as_str(), gcc.line, gcc.col),
+            ("src/main.c", 10, 5)
+        );
+        let windows = ExternalPosition::parse(r"C:\src\main.c:3:1: warning").unwrap();
+        assert_eq!((windows.path.as_str(), windows.line), (r"C:\src\main.c", 3));
+        let tsc = ExternalPosition::parse("app/index.ts(4,12): error TS2322").unwrap();
+        assert_eq!(
+            (tsc.path.as_str(), tsc.line, tsc.col),
+            ("app/index.ts", 4, 12)
+        );
+        let no_col = ExternalPosition::parse("Makefile:7: *** missing separator").unwrap();
+        assert_eq!((no_col.line, no_col.col), (7, 1));
+        assert_eq!(gcc.to_string(), "src/main.c:10:5");
+
+        let parens_in_message = ExternalPosition::parse("a.c:2:3: error: f(1,2)").unwrap();
+        assert_eq!(parens_in_message.path, "a.c");
+        assert_eq!(ExternalPosition::parse("error: no position"), None);
+        assert_eq!(ExternalPosition::parse("a.c:0:1: bad line"), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("lib/util.ts", "x\n");
+        let a = sourcefile.add_file_raw("src/app/main.ts", "let é = 1;\nfoo();\n");
+        sourcefile.add_file_raw("test/main.ts", "y\n");
+
+        assert_eq!(sourcefile.find_file("src/app/main.ts"), Some(a));
+        assert_eq!(
+            sourcefile.find_file("/home/me/proj/src/app/main.ts"),
+            Some(a)
+        );
+        assert_eq!(sourcefile.find_file("./app/main.ts"), Some(a));
+        // Both end in `main.ts`, and nothing else to tell them apart.
+        assert_eq!(sourcefile.find_file("main.ts"), None);
+        assert_eq!(sourcefile.find_file("other.ts"), None);
+
+        let base = sourcefile.file_span(a).start;
+        let eq = ExternalPosition::parse("/build/src/app/main.ts:1:7").unwrap();
+        assert_eq!(
+            sourcefile.from_external(&eq, ColumnEncoding::Chars),
+            Some(base + 7)
+        );
+        assert_eq!(
+            sourcefile.from_external(&eq, ColumnEncoding::Bytes),
+            Some(base + 6)
+        );
+        let mid_char = ExternalPosition::parse("app/main.ts:1:6").unwrap();
+        assert_eq!(
+            sourcefile.from_external(&mid_char, ColumnEncoding::Bytes),
+            None
+        );
+
+        let back = sourcefile
+            .to_external(base + 7, ColumnEncoding::Chars)
+            .unwrap();
+        assert_eq!(back.to_string(), "src/app/main.ts:1:7");
+        let back = sourcefile
+            .to_external(base + 12, ColumnEncoding::Bytes)
+            .unwrap();
+        assert_eq!((back.line, back.col), (2, 1));
+        assert_eq!(
+            sourcefile.to_external(base + 5, ColumnEncoding::Bytes),
+            None
+        );
+    }
+}
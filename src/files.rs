@@ -0,0 +1,266 @@
+//! Iteration over the files that make up a `SourceFile`.
+
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::Range;
+
+use {FileId, GlobalOffsetMap, SourceFile};
+
+/// One of the files in a `SourceFile`, as yielded by `SourceFile::files`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SourceView<'a> {
+    /// The handle of the file.
+    pub id: FileId,
+    /// The name the file was added under.
+    pub name: &'a str,
+    /// The byte range of the file in `contents`.
+    pub range: Range<usize>,
+    /// The range of the file's lines among the lines of all files, counting from 0 at the first
+    /// line of the first file. Each file starts on a new line, even if the one before it doesn't
+    /// end with a newline.
+    pub lines: Range<usize>,
+    /// The text of the file.
+    pub contents: &'a str,
+}
+
+/// An iterator over the files in a `SourceFile`, in the order they were added.
+#[derive(Debug, Clone)]
+pub struct Files<'a> {
+    sourcefile: &'a SourceFile,
+    file_idx: usize,
+}
+
+impl<'a> Iterator for Files<'a> {
+    type Item = SourceView<'a>;
+
+    fn next(&mut self) -> Option<SourceView<'a>> {
+        let sourcefile = self.sourcefile;
+        let name = sourcefile.file_names.get(self.file_idx)?;
+        let id = FileId(self.file_idx);
+        let range = sourcefile.file_span(id);
+        let view = SourceView {
+            id,
+            name,
+            contents: &sourcefile.contents[range.clone()],
+            range,
+            lines: sourcefile.file_line_indices(id),
+        };
+        self.file_idx += 1;
+        Some(view)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.sourcefile.file_names.len() - self.file_idx;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for Files<'a> {}
+
+impl SourceFile {
+    /// Iterate over the files, in the order they were added.
+    pub fn files<'a>(&'a self) -> Files<'a> {
+        Files {
+            sourcefile: self,
+            file_idx: 0,
+        }
+    }
+}
+
+impl SourceFile {
+    /// Split into one single-file `SourceFile` per file, in order.
+    ///
+    /// Each keeps the file's name, path, language, snippet option, replaced ranges and line
+    /// annotations, and the settings of `self`. The text of each file is copied. Provenance from
+    /// `duplicate_region` is dropped, since it refers to offsets in `self`.
+    pub fn explode(&self) -> Vec<SourceFile> {
+        self.files()
+            .map(|file| {
+                let idx = file.id.0;
+                let mut single = SourceFile {
+                    display_base: self.display_base.clone(),
+                    tab_width: self.tab_width,
+                    strip_bom: self.strip_bom,
+                    normalize_line_endings: self.normalize_line_endings,
+                    line_chars: self.line_chars.as_ref().map(|_| Vec::new()),
+                    ..SourceFile::default()
+                };
+                single.push_file(file.name.to_string(), file.contents);
+                single.file_paths[0] = self.file_paths[idx].clone();
+                single.file_replacements[0] = self.file_replacements[idx].clone();
+                single.file_origins[0] = self.file_origins[idx].clone();
+                single.file_annotations[0] = self.file_annotations[idx].clone();
+                single.file_languages[0] = self.file_languages[idx].clone();
+                single.file_snippet_options[0] = self.file_snippet_options[idx].clone();
+                single
+            })
+            .collect()
+    }
+}
+
+impl SourceFile {
+    /// Reorder the files, e.g. by name so a concatenation built in parallel comes out the same
+    /// every time.
+    ///
+    /// The sort is stable, so files that compare equal keep their relative order. Returns a map
+    /// from offsets before the sort to offsets after it. `FileId`s refer to files by position, so
+    /// they refer to the files in their new order afterwards, and the revision changes.
+    pub fn sort_files_by<F>(&mut self, mut compare: F) -> GlobalOffsetMap
+    where
+        F: FnMut(&SourceView, &SourceView) -> Ordering,
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("sort_files_by", files = self.file_names.len()).entered();
+        let (order, ranges) = {
+            let views: Vec<SourceView> = self.files().collect();
+            let mut order: Vec<usize> = (0..views.len()).collect();
+            order.sort_by(|&a, &b| compare(&views[a], &views[b]));
+            let ranges: Vec<Range<usize>> = views.into_iter().map(|file| file.range).collect();
+            (order, ranges)
+        };
+        let line_ranges: Vec<Range<usize>> = (0..order.len())
+            .map(|idx| self.file_line_indices(FileId(idx)))
+            .collect();
+
+        let mut targets = vec![None; order.len()];
+        let mut offset = 0;
+        for &idx in &order {
+            targets[idx] = Some(offset);
+            offset += ranges[idx].len();
+        }
+        let remap = GlobalOffsetMap::from_files(ranges.iter().cloned().zip(targets).collect());
+
+        fn permute<T>(items: &mut Vec<T>, order: &[usize]) {
+            let mut old: Vec<Option<T>> = mem::take(items).into_iter().map(Some).collect();
+            items.extend(order.iter().map(|&idx| old[idx].take().unwrap()));
+        }
+        let mut contents = String::with_capacity(self.contents.len());
+        let mut line_ends = Vec::with_capacity(self.line_ends.len());
+        let mut file_line_ends = Vec::with_capacity(self.file_line_ends.len());
+        let mut file_ends = Vec::with_capacity(self.file_ends.len());
+        let mut line_chars = self
+            .line_chars
+            .as_ref()
+            .map(|line_chars| Vec::with_capacity(line_chars.len()));
+        for &idx in &order {
+            let (old_start, new_start) = (ranges[idx].start, contents.len());
+            contents.push_str(&self.contents[ranges[idx].clone()]);
+            line_ends.extend(
+                self.line_ends[line_ranges[idx].clone()]
+                    .iter()
+                    .map(|end| end - old_start + new_start),
+            );
+            file_line_ends.push(line_ends.len());
+            file_ends.push(contents.len());
+            if let (Some(new), Some(old)) = (line_chars.as_mut(), self.line_chars.as_ref()) {
+                new.extend_from_slice(&old[line_ranges[idx].clone()]);
+            }
+        }
+        self.contents = contents;
+        self.line_ends = line_ends;
+        self.file_line_ends = file_line_ends;
+        self.file_ends = file_ends;
+        self.line_chars = line_chars;
+        permute(&mut self.file_names, &order);
+        permute(&mut self.file_paths, &order);
+        permute(&mut self.file_replacements, &order);
+        permute(&mut self.file_origins, &order);
+        permute(&mut self.file_annotations, &order);
+        permute(&mut self.file_provenance, &order);
+        permute(&mut self.file_languages, &order);
+        permute(&mut self.file_snippet_options, &order);
+        for provenance in &mut self.file_provenance {
+            *provenance = provenance.and_then(|(start, end)| remap.map_span(start, end));
+        }
+        self.revision += 1;
+        remap
+    }
+}
+
+impl<'a> IntoIterator for &'a SourceFile {
+    type Item = SourceView<'a>;
+    type IntoIter = Files<'a>;
+
+    fn into_iter(self) -> Files<'a> {
+        self.files()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn files() {
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file_raw("a", "first\nfile\n");
+        sourcefile.add_file_raw("empty", "");
+        let b = sourcefile.add_file_raw("b", "second");
+
+        let mut names = Vec::new();
+        for file in &sourcefile {
+            assert_eq!(&sourcefile.contents[file.range.clone()], file.contents);
+            names.push(file.name);
+        }
+        assert_eq!(names, vec!["a", "empty", "b"]);
+
+        let views: Vec<_> = sourcefile.files().collect();
+        assert_eq!(views.len(), 3);
+        assert_eq!((views[0].id, views[0].range.clone()), (a, 0..11));
+        assert_eq!((views[2].id, views[2].contents), (b, "second"));
+        assert_eq!(views[1].range, 11..11);
+        assert_eq!(
+            (
+                views[0].lines.clone(),
+                views[1].lines.clone(),
+                views[2].lines.clone()
+            ),
+            (0..2, 2..2, 2..3)
+        );
+    }
+
+    #[test]
+    fn explode() {
+        let mut sourcefile = SourceFile::with_char_counts();
+        sourcefile.add_file_raw("a", "first\nfile\n");
+        let b = sourcefile.add_file_raw("b", "second\nfile\n");
+        sourcefile.set_file_language(b, "txt");
+
+        let parts = sourcefile.explode();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].contents, "second\nfile\n");
+        let pos = parts[1].resolve_offset(8).unwrap();
+        assert_eq!((pos.filename, pos.line, pos.col), ("b", 1, 1));
+        let id = parts[1].files().next().unwrap().id;
+        assert_eq!(parts[1].file_language(id), Some("txt"));
+        assert_eq!(parts[1].line_char_len(id, 0), Some(7));
+    }
+
+    #[test]
+    fn sort_files_by() {
+        let mut sourcefile = SourceFile::with_char_counts();
+        sourcefile.add_file_raw("c", "third\n");
+        let b = sourcefile.add_file_raw("b", "sécond\nfile\n");
+        sourcefile.add_file_raw("a", "first\n");
+        sourcefile.set_file_language(b, "txt");
+        let copy = sourcefile.duplicate_region(6, 14, "copy").unwrap();
+        let revision = sourcefile.revision();
+
+        let remap = sourcefile.sort_files_by(|x, y| x.name.cmp(y.name));
+        assert_eq!(sourcefile.contents, "first\nsécond\nfile\nthird\nsécond\n");
+        assert_ne!(sourcefile.revision(), revision);
+        let names: Vec<_> = sourcefile.files().map(|file| file.name).collect();
+        assert_eq!(names, ["a", "b", "c", "copy"]);
+
+        // "file" in b, and "third" in c.
+        assert_eq!(remap.map(14), Some(14));
+        assert_eq!(remap.map(0), Some(19));
+        let pos = sourcefile.resolve_offset(14).unwrap();
+        assert_eq!((pos.filename, pos.line, pos.col), ("b", 1, 0));
+        let b = sourcefile.files().nth(1).unwrap().id;
+        assert_eq!(sourcefile.file_language(b), Some("txt"));
+        assert_eq!(sourcefile.line_char_len(b, 0), Some(7));
+        assert_eq!(sourcefile.provenance(copy), Some((6, 14)));
+        assert_eq!(sourcefile.trace_to_origin(28), Some(9));
+    }
+}
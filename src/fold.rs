@@ -0,0 +1,143 @@
+//! Folded views of a file, where regions of lines are collapsed into one.
+
+use std::ops::Range;
+
+use {FileId, SourceFile};
+
+/// A file with some line regions collapsed, see `SourceFile::fold_file`.
+///
+/// Line numbers are 0-indexed and local to the file, as in `Position`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FoldedView {
+    /// The text of the folded file.
+    pub text: String,
+    /// For each line of `text`, the range of original lines it shows.
+    line_map: Vec<Range<usize>>,
+}
+
+impl FoldedView {
+    /// The number of lines in the folded view.
+    pub fn line_count(&self) -> usize {
+        self.line_map.len()
+    }
+
+    /// The original lines shown on a line of the folded view. The range has more than one line
+    /// if the line is a collapsed region.
+    pub fn original_lines(&self, folded_line: usize) -> Option<Range<usize>> {
+        self.line_map.get(folded_line).cloned()
+    }
+
+    /// The line of the folded view showing an original line.
+    pub fn folded_line(&self, line: usize) -> Option<usize> {
+        let idx = self.line_map.partition_point(|range| range.end <= line);
+        if idx < self.line_map.len() && self.line_map[idx].start <= line {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+
+    /// Translate an original line and column into folded coordinates.
+    ///
+    /// The column is `None` if the position is hidden inside a collapsed region. Positions on the
+    /// first line of a region keep their column, since that line is shown.
+    pub fn to_folded(&self, line: usize, col: usize) -> Option<(usize, Option<usize>)> {
+        let folded = self.folded_line(line)?;
+        if self.line_map[folded].start == line {
+            Some((folded, Some(col)))
+        } else {
+            Some((folded, None))
+        }
+    }
+}
+
+impl SourceFile {
+    /// Build a view of a file with the given ranges of lines collapsed.
+    ///
+    /// Each region is shown as its first line followed by `placeholder`. Overlapping or nested
+    /// regions are merged, and regions of fewer than two lines are left alone.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn fold_file(&self, id: FileId, folds: &[Range<usize>], placeholder: &str) -> FoldedView {
+        let lines = self.file_line_indices(id);
+        let line_count = lines.len();
+
+        let mut folds: Vec<Range<usize>> = folds
+            .iter()
+            .map(|fold| fold.start.min(line_count)..fold.end.min(line_count))
+            .filter(|fold| fold.end > fold.start + 1)
+            .collect();
+        folds.sort_by_key(|fold| fold.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(folds.len());
+        for fold in folds {
+            match merged.last_mut() {
+                Some(last) if fold.start < last.end => last.end = last.end.max(fold.end),
+                _ => merged.push(fold),
+            }
+        }
+
+        let line_text = |line: usize| {
+            let line_idx = lines.start + line;
+            &self.contents[self.line_offset(line_idx)..self.line_ends[line_idx]]
+        };
+
+        let mut text = String::with_capacity(self.file_span(id).len());
+        let mut line_map = Vec::with_capacity(line_count);
+        let mut merged = merged.into_iter().peekable();
+        let mut line = 0;
+        while line < line_count {
+            match merged.peek() {
+                Some(fold) if fold.start == line => {
+                    let first = line_text(line);
+                    text.push_str(first.trim_end_matches(['\n', '\r']));
+                    text.push_str(placeholder);
+                    // Only end with a newline if the region did.
+                    if line_text(fold.end - 1).ends_with('\n') {
+                        text.push('\n');
+                    }
+                    line_map.push(fold.clone());
+                    line = fold.end;
+                    merged.next();
+                }
+                _ => {
+                    text.push_str(line_text(line));
+                    line_map.push(line..line + 1);
+                    line += 1;
+                }
+            }
+        }
+
+        FoldedView { text, line_map }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn fold() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("other", "x\n");
+        let id = sourcefile.add_file_raw("a", "fn a() {\n    1\n}\nfn b() {\n    2\n}\n");
+        let view = sourcefile.fold_file(id, &[0..3, 3..6, 4..5], " ... }");
+        assert_eq!(view.text, "fn a() { ... }\nfn b() { ... }\n");
+        assert_eq!(view.line_count(), 2);
+        assert_eq!(view.original_lines(1), Some(3..6));
+        assert_eq!(view.folded_line(4), Some(1));
+        assert_eq!(view.folded_line(6), None);
+        assert_eq!(view.to_folded(3, 2), Some((1, Some(2))));
+        assert_eq!(view.to_folded(4, 2), Some((1, None)));
+    }
+
+    #[test]
+    fn overlapping_folds() {
+        let mut sourcefile = SourceFile::new();
+        let id = sourcefile.add_file_raw("a", "a\nb\nc\nd\ne");
+        let view = sourcefile.fold_file(id, &[1..3, 2..5], "..");
+        assert_eq!(view.text, "a\nb..");
+        assert_eq!(view.original_lines(1), Some(1..5));
+    }
+}
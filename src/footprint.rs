@@ -0,0 +1,119 @@
+//! Reporting how much memory a `SourceFile` is using.
+
+use std::mem::size_of;
+
+use origin::OriginMap;
+use SourceFile;
+
+/// The heap memory used by a `SourceFile`, in bytes. See `SourceFile::memory_footprint`.
+///
+/// Sizes count allocated capacity, not just the part in use.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MemoryFootprint {
+    /// The concatenated text.
+    pub contents: usize,
+    /// The line and file tables used to resolve offsets.
+    pub index: usize,
+    /// File names and paths.
+    pub names: usize,
+    /// Other per-file information, such as languages, line annotations and origin maps.
+    pub metadata: usize,
+}
+
+impl MemoryFootprint {
+    /// The total over all categories.
+    pub fn total(&self) -> usize {
+        self.contents + self.index + self.names + self.metadata
+    }
+}
+
+impl SourceFile {
+    /// Report the heap memory used, broken down by what it is used for.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let usizes = |len: usize| len * size_of::<usize>();
+
+        let index = usizes(self.file_line_ends.capacity())
+            + usizes(self.file_ends.capacity())
+            + usizes(self.line_ends.capacity())
+            + self
+                .line_chars
+                .as_ref()
+                .map_or(0, |line_chars| usizes(line_chars.capacity()));
+
+        let names = self.file_names.capacity() * size_of::<String>()
+            + self.file_names.iter().map(String::capacity).sum::<usize>()
+            + self.file_paths.capacity() * size_of::<Option<(std::path::PathBuf, String)>>()
+            + self
+                .file_paths
+                .iter()
+                .flatten()
+                .map(|(path, given)| path.capacity() + given.capacity())
+                .sum::<usize>()
+            + self.display_base.as_ref().map_or(0, |base| base.capacity());
+
+        let metadata = self.file_languages.capacity() * size_of::<Option<String>>()
+            + self
+                .file_languages
+                .iter()
+                .flatten()
+                .map(String::capacity)
+                .sum::<usize>()
+            + self.file_snippet_options.capacity() * size_of::<Option<String>>()
+            + self
+                .file_snippet_options
+                .iter()
+                .flatten()
+                .map(String::capacity)
+                .sum::<usize>()
+            + self.file_replacements.capacity() * size_of::<Vec<(usize, usize)>>()
+            + self
+                .file_replacements
+                .iter()
+                .map(|ranges| ranges.capacity() * size_of::<(usize, usize)>())
+                .sum::<usize>()
+            + self.file_annotations.capacity() * size_of::<Vec<String>>()
+            + self
+                .file_annotations
+                .iter()
+                .map(|annotations| {
+                    annotations.capacity() * size_of::<String>()
+                        + annotations.iter().map(String::capacity).sum::<usize>()
+                })
+                .sum::<usize>()
+            + self.file_provenance.capacity() * size_of::<Option<(usize, usize)>>()
+            + self.file_origins.capacity() * size_of::<OriginMap>()
+            + self
+                .file_origins
+                .iter()
+                .map(OriginMap::heap_size)
+                .sum::<usize>();
+
+        MemoryFootprint {
+            contents: self.contents.capacity(),
+            index,
+            names,
+            metadata,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn footprint() {
+        let mut sourcefile = SourceFile::new();
+        assert_eq!(sourcefile.memory_footprint().total(), 0);
+
+        sourcefile.add_file_raw("name", "some\ntext\n");
+        let footprint = sourcefile.memory_footprint();
+        assert!(footprint.contents >= 10);
+        assert!(footprint.index >= 3 * std::mem::size_of::<usize>());
+        assert!(footprint.names >= 4);
+        assert_eq!(
+            footprint.total(),
+            footprint.contents + footprint.index + footprint.names + footprint.metadata
+        );
+    }
+}
@@ -1,20 +1,185 @@
 //! A library providing `SourceFiles`, a concatenated list of files with information for resolving
 //! points and spans.
 
-use std::path::Path;
+#[cfg(feature = "annotate-snippets")]
+extern crate annotate_snippets;
+#[cfg(feature = "ariadne")]
+extern crate ariadne;
+#[cfg(feature = "codespan-reporting")]
+extern crate codespan_reporting;
+#[cfg(feature = "miette")]
+extern crate miette;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+#[cfg(feature = "toml")]
+extern crate toml;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "unicode-normalization")]
+extern crate unicode_normalization;
+#[cfg(feature = "unicode-segmentation")]
+extern crate unicode_segmentation;
+#[cfg(feature = "unicode-width")]
+extern crate unicode_width;
+
+mod annotate;
+#[cfg(feature = "annotate-snippets")]
+mod annotate_snippet;
+#[cfg(feature = "ariadne")]
+mod ariadne_cache;
+mod blame;
+mod bom;
+#[cfg(feature = "codespan-reporting")]
+mod codespan;
+mod column;
+mod context;
+#[cfg(any(test, feature = "corpus"))]
+mod corpus;
+mod cursor;
+mod dedup;
+mod delta;
+mod error;
+mod external;
+mod files;
+mod fold;
+mod footprint;
+mod line_hash;
+mod line_set;
+mod lines;
+#[cfg(feature = "manifest")]
+mod manifest;
+mod message;
+#[cfg(feature = "miette")]
+mod miette_source;
+mod navigate;
+mod newline;
+#[cfg(feature = "unicode-normalization")]
+mod normalize;
+mod notation;
+mod offset_map;
+mod option_snippet;
+mod origin;
+mod outline;
+mod owned;
+mod persist;
+mod provenance;
+mod reader;
+mod resolver;
+mod revision;
+mod sanitize;
+mod sealed;
+#[cfg(feature = "serde")]
+mod serialize;
+mod side_table;
+mod snap;
+#[cfg(feature = "sourcemap")]
+mod sourcemap;
+mod span_end;
+mod span_tree;
+mod spanned;
+mod spans;
+mod style;
+mod verify;
+mod wire;
+mod wrap;
+
+#[cfg(feature = "ariadne")]
+pub use ariadne_cache::AriadneCache;
+pub use column::ColumnEncoding;
+pub use context::{Snippet, SnippetLine, SpanContext};
+#[cfg(any(test, feature = "corpus"))]
+pub use corpus::{Corpus, CorpusConfig, CorpusPosition};
+pub use cursor::SourceCursor;
+pub use dedup::DedupReport;
+pub use delta::DeltaMap;
+pub use error::{AddError, Error};
+pub use external::ExternalPosition;
+pub use files::{Files, SourceView};
+pub use fold::FoldedView;
+pub use footprint::MemoryFootprint;
+pub use line_hash::LineHashIndex;
+pub use line_set::LineSet;
+pub use lines::{FileLines, LineChunk, LineChunks, LineInfo, LineTables};
+#[cfg(feature = "manifest")]
+pub use manifest::{Manifest, ManifestError, ManifestFile};
+pub use notation::{ParseSpanNotationError, SpanNotation};
+pub use offset_map::GlobalOffsetMap;
+pub use outline::{Outline, OutlineRegion};
+pub use owned::{OwnedPosition, OwnedSpan};
+pub use resolver::Resolver;
+pub use revision::{Stale, TaggedSpan};
+pub use sanitize::SanitizedLine;
+pub use sealed::Sealed;
+pub use side_table::{SpanId, SpanSideTable};
+pub use snap::SnapMode;
+#[cfg(feature = "sourcemap")]
+pub use sourcemap::{SourceMapError, SourceMapFile};
+pub use span_end::SpanEndPolicy;
+pub use span_tree::SpanTree;
+pub use spanned::SpannedError;
+pub use style::{FileStyle, Indent, LineEnding};
+pub use verify::IndexDivergence;
+pub use wire::{SpanReader, SpanRecord, SpanWriter};
+pub use wrap::WrappedLine;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::{fmt, fs, io};
 
 /// A concatenated string of files, with sourcemap information.
+///
+/// With the `serde` feature, this can be serialized with its index and all, so it needn't be
+/// rebuilt from the files. Deserializing checks that the index describes `contents`.
 #[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "serialize::Unchecked")
+)]
 pub struct SourceFile {
     /// The full contents of all the files
     pub contents: String,
     /// The names of the files (same length as `files`).
     file_names: Vec<String>,
-    /// The number of lines in each file.
-    file_lines: Vec<usize>,
-    /// The length of each line in all source files
-    line_lengths: Vec<usize>,
+    /// The absolute path of each file, if it was read from disk, and the name it was added under.
+    file_paths: Vec<Option<(PathBuf, String)>>,
+    /// If set, names of files under this directory are displayed relative to it.
+    display_base: Option<PathBuf>,
+    /// If set, tabs in columns from `expanded_col` advance to the next multiple of this.
+    tab_width: Option<usize>,
+    /// If set, byte order marks are dropped from the start of files rather than kept.
+    strip_bom: bool,
+    /// If set, `\r\n` and lone `\r` line endings are replaced with `\n` as files are added.
+    normalize_line_endings: bool,
+    /// The byte ranges, local to each file, where invalid UTF-8 was replaced with U+FFFD.
+    file_replacements: Vec<Vec<(usize, usize)>>,
+    /// For each file, how offsets into its text map back to the bytes it was added with.
+    file_origins: Vec<origin::OriginMap>,
+    /// For files copied from another region with `duplicate_region`, the span they came from.
+    file_provenance: Vec<Option<(usize, usize)>>,
+    /// Caller-supplied annotations for each line of each file, e.g. blame commit ids.
+    file_annotations: Vec<Vec<String>>,
+    /// The language tag of each file, if known.
+    file_languages: Vec<Option<String>>,
+    /// For snippets added with `add_option_snippet`, the option they came from.
+    file_snippet_options: Vec<Option<String>>,
+    /// For each file, the global index one past its last line. A file's first line is where the
+    /// previous file's lines end.
+    file_line_ends: Vec<usize>,
+    /// For each file, the offset one past its last byte. A file starts where the previous file
+    /// ends.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    file_ends: Vec<usize>,
+    /// For each line in all source files, the offset one past its last byte (including its line
+    /// ending). A line starts where the previous line ends.
+    line_ends: Vec<usize>,
+    /// The number of chars in each line in all source files, if we are tracking them.
+    line_chars: Option<Vec<usize>>,
+    /// Bumped whenever previously valid offsets may have changed meaning.
+    revision: u64,
 }
 
 impl SourceFile {
@@ -23,100 +188,632 @@ impl SourceFile {
         Default::default()
     }
 
+    /// Create a new empty sourcefile that also records the number of chars in each line.
+    ///
+    /// This makes char-based queries like `char_len` and `line_char_len` cheap, at the cost of
+    /// scanning the text of every file as it is added.
+    pub fn with_char_counts() -> Self {
+        SourceFile {
+            line_chars: Some(Vec::new()),
+            ..Default::default()
+        }
+    }
+
     /// Concatenate a file to the end of `contents`, and record info needed to resolve spans.
     ///
     /// If the last line doesn't end with a newline character, it will still be a 'line' for the
     /// purposes of this calculation.
-    pub fn add_file(&mut self, filename: impl AsRef<Path>) -> io::Result<()> {
+    ///
+    /// The file is tagged with its extension (e.g. `rs`) as its language, see
+    /// `set_file_language`.
+    pub fn add_file(&mut self, filename: impl AsRef<Path>) -> io::Result<FileId> {
         let filename = filename.as_ref();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("add_file", path = %filename.display()).entered();
         let file = fs::read_to_string(filename)?;
+        let absolute = absolute_path(filename)?;
+
+        let id = self.add_file_raw(filename.display(), file);
+        self.file_languages[id.0] = filename
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        if let Some(name) = self.display_name(&absolute) {
+            self.file_names[id.0] = name;
+        }
+        self.file_paths[id.0] = Some((absolute, filename.display().to_string()));
+        Ok(id)
+    }
+
+    /// Remove all files, keeping allocated capacity so the `SourceFile` can be refilled cheaply.
+    ///
+    /// Settings such as the display base and char count tracking are kept. `FileId`s handed out
+    /// before clearing refer to whatever files are added afterwards, if any.
+    pub fn clear(&mut self) {
+        self.truncate_files(0);
+    }
 
-        // We should skip this file if it is completely empty.
-        self.add_file_raw(filename.display(), file);
+    /// Add a file, run `f` with it, then remove it again.
+    ///
+    /// This is for tests that share one `SourceFile` fixture but each need an extra file. Any
+    /// files `f` adds are removed too. As with `clear`, the revision changes when the file is
+    /// removed.
+    pub fn with_file<R, F>(&mut self, name: impl fmt::Display, contents: &str, f: F) -> R
+    where
+        F: FnOnce(&mut SourceFile, FileId) -> R,
+    {
+        let files = self.file_names.len();
+        let id = self.ingest_file(name.to_string(), contents);
+        let result = f(self, id);
+        self.truncate_files(files);
+        result
+    }
+
+    /// Remove all but the first `len` files.
+    fn truncate_files(&mut self, len: usize) {
+        if len >= self.file_names.len() {
+            return;
+        }
+        let lines = self.file_line_start(len);
+        let bytes = self.line_offset(lines);
+        self.contents.truncate(bytes);
+        self.file_names.truncate(len);
+        self.file_paths.truncate(len);
+        self.file_replacements.truncate(len);
+        self.file_origins.truncate(len);
+        self.file_annotations.truncate(len);
+        self.file_provenance.truncate(len);
+        self.file_languages.truncate(len);
+        self.file_snippet_options.truncate(len);
+        self.file_line_ends.truncate(len);
+        self.file_ends.truncate(len);
+        self.line_ends.truncate(lines);
+        if let Some(ref mut line_chars) = self.line_chars {
+            line_chars.truncate(lines);
+        }
+        self.revision += 1;
+    }
+
+    /// Display the names of files under `base` relative to it, e.g. `src/lib.rs` rather than
+    /// `/home/me/project/src/lib.rs`.
+    ///
+    /// This applies to files already added and to files added later, but only to those read with
+    /// `add_file`. Their absolute paths are still available from `file_path`.
+    pub fn set_display_base(&mut self, base: impl AsRef<Path>) -> io::Result<()> {
+        self.display_base = Some(absolute_path(base.as_ref())?);
+        for idx in 0..self.file_names.len() {
+            let name = match self.file_paths[idx] {
+                Some((ref absolute, ref given)) => {
+                    self.display_name(absolute).unwrap_or_else(|| given.clone())
+                }
+                None => continue,
+            };
+            self.file_names[idx] = name;
+        }
         Ok(())
     }
 
-    pub fn add_file_raw(&mut self, name: impl fmt::Display, contents: impl Into<String>) {
+    /// The name to display for a file at `absolute`, if it is under the display base.
+    fn display_name(&self, absolute: &Path) -> Option<String> {
+        let base = self.display_base.as_ref()?;
+        let relative = absolute.strip_prefix(base).ok()?;
+        Some(relative.display().to_string())
+    }
+
+    /// The absolute path of a file, if it was read from disk with `add_file`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn file_path(&self, id: FileId) -> Option<&Path> {
+        self.file_paths[id.0]
+            .as_ref()
+            .map(|(absolute, _)| absolute.as_path())
+    }
+
+    /// Create a sourcefile from `(name, contents)` pairs, as if each was added with
+    /// `add_file_raw`.
+    ///
+    /// This reserves space for all the text up front, so is faster than adding the files one at a
+    /// time.
+    pub fn from_raw_files<I, N, C>(files: I) -> Self
+    where
+        I: IntoIterator<Item = (N, C)>,
+        N: fmt::Display,
+        C: AsRef<str>,
+    {
+        let files: Vec<(N, C)> = files.into_iter().collect();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("from_raw_files", files = files.len()).entered();
+        let mut sourcefile = SourceFile::new();
+        sourcefile.contents.reserve(
+            files
+                .iter()
+                .map(|(_, contents)| contents.as_ref().len())
+                .sum(),
+        );
+        sourcefile.file_names.reserve(files.len());
+        sourcefile.file_paths.reserve(files.len());
+        sourcefile.file_replacements.reserve(files.len());
+        sourcefile.file_origins.reserve(files.len());
+        sourcefile.file_annotations.reserve(files.len());
+        sourcefile.file_provenance.reserve(files.len());
+        sourcefile.file_languages.reserve(files.len());
+        sourcefile.file_snippet_options.reserve(files.len());
+        sourcefile.file_line_ends.reserve(files.len());
+        sourcefile.file_ends.reserve(files.len());
+        for (name, contents) in files {
+            sourcefile.ingest_file(name.to_string(), contents.as_ref());
+        }
+        sourcefile
+    }
+
+    /// Concatenate the given contents to the end of `contents` under the name `name`, without
+    /// touching the filesystem.
+    ///
+    /// An empty file is still recorded, but no offset will ever resolve into it. Use
+    /// `try_add_file_raw` to treat this as an error. A byte order mark at the start may be
+    /// dropped, see `set_strip_bom`, and line endings may be normalized, see
+    /// `set_normalize_line_endings`.
+    pub fn add_file_raw(&mut self, name: impl fmt::Display, contents: impl Into<String>) -> FileId {
         let contents = contents.into();
-        // We should skip this file if it is completely empty (There are no offsets that index into this file).
-        if contents.is_empty() {
-            return;
+        self.ingest_file(name.to_string(), &contents)
+    }
+
+    /// Like `add_file_raw`, but refusing content that no offset could resolve into.
+    ///
+    /// Nothing is added if this returns an error.
+    pub fn try_add_file_raw(
+        &mut self,
+        name: impl fmt::Display,
+        contents: impl Into<String>,
+    ) -> Result<FileId, AddError> {
+        let contents = contents.into();
+        if contents.len() == self.bom_len(&contents) {
+            return Err(AddError::Empty);
         }
+        Ok(self.ingest_file(name.to_string(), &contents))
+    }
 
-        let mut num_lines = 0;
+    /// Append `contents` as a new file, dropping its byte order mark and normalizing its line
+    /// endings if those are set, and recording how its offsets map back to `contents`.
+    fn ingest_file(&mut self, name: String, contents: &str) -> FileId {
+        let bom_len = self.bom_len(contents);
+        let contents = &contents[bom_len..];
+        let mut origins = origin::OriginMap::default();
+        origins.push(0, bom_len, true);
+        let id = if self.normalize_line_endings && contents.contains('\r') {
+            let normalized = newline::normalize_newlines(contents, bom_len, &mut origins);
+            self.push_file(name, &normalized)
+        } else {
+            self.push_file(name, contents)
+        };
+        self.file_origins[id.0] = origins;
+        id
+    }
+
+    /// Index `contents` and append it as a new file.
+    fn push_file(&mut self, name: String, contents: &str) -> FileId {
         // We can't use str::lines because we won't know if 1 or 2 chars were lost (if there was a \r).
         let mut lines = contents.split('\n').peekable();
         while let Some(line) = lines.next() {
-            if lines.peek().is_some() {
+            let newline = if lines.peek().is_some() {
                 // middle line
-                num_lines += 1;
-                self.line_lengths.push(line.len() + 1);
+                1
             } else if line.is_empty() {
                 // last line is empty, skip it
+                continue;
             } else {
                 // last line not empty, but no \n at the end.
-                num_lines += 1;
-                self.line_lengths.push(line.len());
+                0
+            };
+            let line_end = self.line_offset(self.line_ends.len()) + line.len() + newline;
+            self.line_ends.push(line_end);
+            if let Some(ref mut line_chars) = self.line_chars {
+                line_chars.push(line.chars().count() + newline);
             }
         }
 
+        let id = self.push_file_entry(name);
+        self.contents += contents;
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            file = %self.file_names[id.0],
+            bytes = contents.len(),
+            lines = self.line_count(id),
+            "indexed file"
+        );
+        id
+    }
+
+    /// Record a new file called `name`, whose lines have just been pushed onto `line_ends`.
+    fn push_file_entry(&mut self, name: String) -> FileId {
+        let id = FileId(self.file_names.len());
         // Record the name
-        self.file_names.push(name.to_string());
-        // Record the number of lines
-        self.file_lines.push(num_lines);
-        self.contents += &contents;
+        self.file_names.push(name);
+        self.file_paths.push(None);
+        self.file_replacements.push(Vec::new());
+        self.file_origins.push(Default::default());
+        self.file_annotations.push(Vec::new());
+        self.file_provenance.push(None);
+        self.file_languages.push(None);
+        self.file_snippet_options.push(None);
+        // Record where its lines end
+        self.file_line_ends.push(self.line_ends.len());
+        self.file_ends.push(self.line_offset(self.line_ends.len()));
+        id
     }
 
-    /// Get the file, line, and col position of a byte offset.
+    /// The name a file was added under.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    #[inline]
+    pub fn file_name(&self, id: FileId) -> &str {
+        &self.file_names[id.0]
+    }
+
+    /// The number of lines in a file.
+    ///
+    /// This is recorded when the file is added, so it doesn't rescan the file's text. As for
+    /// resolution, a final line without a trailing newline still counts as a line.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    #[inline]
+    pub fn line_count(&self, id: FileId) -> usize {
+        self.file_line_indices(id).len()
+    }
+
+    /// The byte range of a file in `contents`, e.g. for slicing out its text to re-lex it.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    #[inline]
+    pub fn file_span(&self, id: FileId) -> std::ops::Range<usize> {
+        let lines = self.file_line_indices(id);
+        self.line_offset(lines.start)..self.line_offset(lines.end)
+    }
+
+    /// The text of a file, as a slice of `contents`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    #[inline]
+    pub fn file_contents(&self, id: FileId) -> &str {
+        &self.contents[self.file_span(id)]
+    }
+
+    /// The text of the span `start..end`, or `None` if it is reversed, runs past the end of
+    /// `contents`, or either end isn't on a char boundary.
+    #[inline]
+    pub fn span_text(&self, start: usize, end: usize) -> Option<&str> {
+        self.contents.get(start..end)
+    }
+
+    /// The byte offset in `contents` of the start of a 0-indexed line of a file.
+    ///
+    /// This is O(1), since the index stores where each line starts.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    #[inline]
+    pub fn line_start(&self, id: FileId, line: usize) -> Option<usize> {
+        let lines = self.file_line_indices(id);
+        if line >= lines.len() {
+            return None;
+        }
+        Some(self.line_offset(lines.start + line))
+    }
+
+    /// The byte range in `contents` of the line containing `offset`, including its line ending.
+    ///
+    /// This is a binary search, like `resolve_offset`, but doesn't work out the file or column.
+    pub fn line_range(&self, offset: usize) -> Option<std::ops::Range<usize>> {
+        let (line_idx, line_start) = self.locate_line(offset)?;
+        Some(line_start..self.line_ends[line_idx])
+    }
+
+    /// The number of chars in `contents`.
+    ///
+    /// This is O(number of lines) if the `SourceFile` was created with `with_char_counts`, and
+    /// rescans `contents` otherwise.
+    pub fn char_len(&self) -> usize {
+        match self.line_chars {
+            Some(ref line_chars) => line_chars.iter().sum(),
+            None => self.contents.chars().count(),
+        }
+    }
+
+    /// The number of chars in a line of a file, including its line ending.
+    ///
+    /// This is O(1) if the `SourceFile` was created with `with_char_counts`, and rescans the line
+    /// otherwise.
     ///
     /// # Panics
     ///
-    /// This function will panic if `offset` is not on a character boundary.
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn line_char_len(&self, id: FileId, line: usize) -> Option<usize> {
+        let lines = self.file_line_indices(id);
+        if line >= lines.len() {
+            return None;
+        }
+        let line_idx = lines.start + line;
+        Some(match self.line_chars {
+            Some(ref line_chars) => line_chars[line_idx],
+            None => {
+                let start = self.line_offset(line_idx);
+                self.contents[start..self.line_ends[line_idx]]
+                    .chars()
+                    .count()
+            }
+        })
+    }
+
+    /// Tag a file with a language (e.g. `sql` for a query embedded in a Rust file), replacing
+    /// any previous tag.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn set_file_language(&mut self, id: FileId, language: impl Into<String>) {
+        self.file_languages[id.0] = Some(language.into());
+    }
+
+    /// The language a file is tagged with, if any.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    #[inline]
+    pub fn file_language(&self, id: FileId) -> Option<&str> {
+        self.file_languages[id.0].as_deref()
+    }
+
+    /// Iterate over the files tagged with `language`, in the order they were added.
+    pub fn files_with_language<'a>(
+        &'a self,
+        language: &'a str,
+    ) -> impl Iterator<Item = FileId> + 'a {
+        self.file_languages
+            .iter()
+            .enumerate()
+            .filter(move |&(_, lang)| lang.as_deref() == Some(language))
+            .map(|(idx, _)| FileId(idx))
+    }
+
+    /// Get the file, line, and col position of a byte offset.
+    ///
+    /// Resolution never allocates: the returned `Position` borrows the file name from `self`.
+    ///
+    /// An offset in the middle of a character resolves to a column in the middle of it, see
+    /// `try_resolve_offset` for a variant that rejects it.
+    #[inline]
     pub fn resolve_offset<'a>(&'a self, offset: usize) -> Option<Position<'a>> {
-        // If there isn't a single line, always return None.
-        let mut line_acc = *self.line_lengths.get(0)?;
-        let mut line_idx = 0;
-        while line_acc <= offset {
-            line_idx += 1;
-            // If we have exhaused all the lines, return None
-            line_acc += *self.line_lengths.get(line_idx)?;
+        self.resolve_offset_id(offset).map(|id| self.position(id))
+    }
+
+    /// Like `resolve_offset`, but giving the file as a `FileId` rather than its name.
+    ///
+    /// The result holds no borrow of `self`, so it can be stored, compared and hashed cheaply,
+    /// and named later with `position`.
+    #[inline]
+    pub fn resolve_offset_id(&self, offset: usize) -> Option<PositionId> {
+        let (line_idx, line_acc, file_idx) = self.locate(offset)?;
+
+        Some(PositionId {
+            file: FileId(file_idx),
+            line: line_idx - self.file_line_start(file_idx),
+            col: offset - line_acc,
+            offset,
+        })
+    }
+
+    /// The `Position` of a `PositionId`, naming its file.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the file of `id` was not returned by this `SourceFile`.
+    #[inline]
+    pub fn position<'a>(&'a self, id: PositionId) -> Position<'a> {
+        Position::new(&self.file_names[id.file.0], id.line, id.col, id.offset)
+    }
+
+    /// Find the index of the file containing a line, and the global index of its first line.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `line_idx` is out of bounds.
+    fn locate_file(&self, line_idx: usize) -> (usize, usize) {
+        // Empty files end where the previous file did, so are never found.
+        let file_idx = self.file_line_ends.partition_point(|&end| end <= line_idx);
+        // Go back to the start of the file (for working out the line).
+        (file_idx, self.file_line_start(file_idx))
+    }
+
+    /// Find the global index and start offset of the line containing `offset`, and the index
+    /// of the file it is in.
+    ///
+    /// Up to `TWO_LEVEL_MIN_LINES` lines, this searches all the line ends, then the file ends.
+    /// Past that, the line ends are spread out enough in memory that it is quicker to find the
+    /// file first, then search only its lines.
+    fn locate(&self, offset: usize) -> Option<(usize, usize, usize)> {
+        if self.line_ends.len() < TWO_LEVEL_MIN_LINES {
+            let (line_idx, line_start) = self.locate_line(offset)?;
+            Some((line_idx, line_start, self.locate_file(line_idx).0))
+        } else {
+            self.locate_two_level(offset)
         }
+    }
+
+    /// `locate` by searching the file ends, then the line ends of the file found.
+    fn locate_two_level(&self, offset: usize) -> Option<(usize, usize, usize)> {
+        // Empty files end where the previous file did, so are never found.
+        let file_idx = self.file_ends.partition_point(|&end| end <= offset);
+        let lines = self.file_line_start(file_idx)..*self.file_line_ends.get(file_idx)?;
+        let line_idx = lines.start + self.line_ends[lines].partition_point(|&end| end <= offset);
+        Some((line_idx, self.line_offset(line_idx), file_idx))
+    }
+
+    /// Find the global index and start offset of the line containing `offset`.
+    fn locate_line(&self, offset: usize) -> Option<(usize, usize)> {
+        let line_idx = self.line_ends.partition_point(|&end| end <= offset);
+        // If we have exhausted all the lines, return None
+        let line_end = *self.line_ends.get(line_idx)?;
         // Go back to the start of the line (for working out the column).
-        line_acc -= self.line_lengths[line_idx];
+        Some((line_idx, line_end - self.line_len(line_idx)))
+    }
+
+    /// The global indices of the lines of a file.
+    fn file_line_indices(&self, id: FileId) -> std::ops::Range<usize> {
+        self.file_line_start(id.0)..self.file_line_ends[id.0]
+    }
 
-        // Can't panic - if we have a line we have a file
-        let mut file_acc = self.file_lines[0];
-        let mut file_idx = 0;
-        while file_acc <= line_idx {
-            file_idx += 1;
-            file_acc += self.file_lines[file_idx];
+    /// The global index of the first line of a file, given its index.
+    ///
+    /// The index may be the number of files, giving the number of lines.
+    fn file_line_start(&self, file_idx: usize) -> usize {
+        if file_idx == 0 {
+            0
+        } else {
+            self.file_line_ends[file_idx - 1]
         }
-        // Go back to the start of the file (for working out the line).
-        file_acc -= self.file_lines[file_idx];
+    }
 
-        Some(Position::new(
-            &self.file_names[file_idx],
-            line_idx - file_acc,
-            offset - line_acc,
-        ))
+    /// The byte offset in `contents` of the start of a line, given its global index.
+    ///
+    /// The index may be the number of lines, giving the length of the text.
+    fn line_offset(&self, line_idx: usize) -> usize {
+        if line_idx == 0 {
+            0
+        } else {
+            self.line_ends[line_idx - 1]
+        }
+    }
+
+    /// The length in bytes of a line, including its line ending, given its global index.
+    fn line_len(&self, line_idx: usize) -> usize {
+        self.line_ends[line_idx] - self.line_offset(line_idx)
+    }
+
+    /// The first file added under `name`.
+    fn file_by_name(&self, name: &str) -> Option<FileId> {
+        self.file_names
+            .iter()
+            .position(|file_name| file_name == name)
+            .map(FileId)
+    }
+
+    /// The offset of a 0-indexed line and byte column within a file.
+    ///
+    /// The column may point just past the end of the line's text, but not into its line ending.
+    fn offset_of_line_col(&self, id: FileId, line: usize, col: usize) -> Option<usize> {
+        let lines = self.file_line_indices(id);
+        if line >= lines.len() {
+            return None;
+        }
+        let line_idx = lines.start + line;
+        let start = self.line_offset(line_idx);
+        let text = &self.contents[start..self.line_ends[line_idx]];
+        let text = text.trim_end_matches(['\n', '\r']);
+        if col > text.len() || !text.is_char_boundary(col) {
+            return None;
+        }
+        Some(start + col)
     }
 
     /// Get the file, line, and col position of each end of a span.
-    // TODO this could be more efficient by using the fact that end is after (and probably near to)
-    // start.
+    ///
+    /// The end is found starting from the line of the start, so when both are on the same line,
+    /// as is usual for diagnostics, this costs hardly more than `resolve_offset`.
+    #[inline]
     pub fn resolve_offset_span<'a>(&'a self, start: usize, end: usize) -> Option<Span<'a>> {
+        let (start, end) = self.resolve_offset_span_ids(start, end)?;
+        Some(Span {
+            start: self.position(start),
+            end: self.position(end),
+        })
+    }
+
+    /// Like `resolve_offset_span`, but giving the files as `FileId`s rather than their names.
+    #[inline]
+    pub fn resolve_offset_span_ids(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> Option<(PositionId, PositionId)> {
         if end < start {
             return None;
         }
-        Some(Span {
-            start: self.resolve_offset(start)?,
-            end: self.resolve_offset(end)?,
-        })
+        let (start_line, start_line_acc, start_file) = self.locate(start)?;
+        let start_file_acc = self.file_line_start(start_file);
+
+        let (end_line, end_line_acc) = if end < self.line_ends[start_line] {
+            (start_line, start_line_acc)
+        } else {
+            let rest = &self.line_ends[start_line..];
+            let line_idx = start_line + rest.partition_point(|&line_end| line_end <= end);
+            let line_end = *self.line_ends.get(line_idx)?;
+            (line_idx, line_end - self.line_len(line_idx))
+        };
+        let (end_file, end_file_acc) = if end_line < self.file_line_ends[start_file] {
+            (start_file, start_file_acc)
+        } else {
+            let rest = &self.file_line_ends[start_file..];
+            let file_idx = start_file + rest.partition_point(|&file_end| file_end <= end_line);
+            (file_idx, self.file_line_start(file_idx))
+        };
+
+        Some((
+            PositionId {
+                file: FileId(start_file),
+                line: start_line - start_file_acc,
+                col: start - start_line_acc,
+                offset: start,
+            },
+            PositionId {
+                file: FileId(end_file),
+                line: end_line - end_file_acc,
+                col: end - end_line_acc,
+                offset: end,
+            },
+        ))
+    }
+
+    /// The byte offset of a 0-indexed line and column in the file called `filename`, the reverse
+    /// of `resolve_offset`.
+    ///
+    /// As in `Position`, the column counts bytes. It may point just past the end of the line's
+    /// text, e.g. for an error about a missing `;`, but not into its line ending or the middle of
+    /// a char. If several files were added under `filename`, the first one is used.
+    pub fn offset_of(&self, filename: &str, line: usize, col: usize) -> Option<usize> {
+        self.offset_of_line_col(self.file_by_name(filename)?, line, col)
     }
 }
 
+/// The number of lines from which `locate` finds the file before the line.
+const TWO_LEVEL_MIN_LINES: usize = 1 << 12;
+
+/// Make `path` absolute by joining it to the current directory, without resolving symlinks.
+fn absolute_path(path: &Path) -> io::Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(std::env::current_dir()?.join(path))
+    }
+}
+
+/// A handle to one of the files in a `SourceFile`, returned when the file is added.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct FileId(usize);
+
 /// A position in a source file.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Position<'a> {
     /// Name of the file the position is in.
     pub filename: &'a str,
@@ -124,32 +821,71 @@ pub struct Position<'a> {
     pub line: usize,
     /// 0-indexed column number of position.
     pub col: usize,
+    /// Byte offset of the position in `contents`.
+    pub offset: usize,
 }
 
 impl<'a> Position<'a> {
-    /// Constructor for tests.
-    fn new(filename: &'a str, line: usize, col: usize) -> Position<'a> {
+    fn new(filename: &'a str, line: usize, col: usize, offset: usize) -> Position<'a> {
         Position {
-            filename: filename.as_ref(),
+            filename,
             line,
             col,
+            offset,
         }
     }
 }
 
+/// A position in a source file, with the file given by its handle, see
+/// `SourceFile::resolve_offset_id`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct PositionId {
+    /// The file the position is in.
+    pub file: FileId,
+    /// 0-indexed line number of position.
+    pub line: usize,
+    /// 0-indexed column number of position.
+    pub col: usize,
+    /// Byte offset of the position in `contents`.
+    pub offset: usize,
+}
+
 /// A span in a source file
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Span<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub start: Position<'a>,
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub end: Position<'a>,
 }
 
+impl<'a> Span<'a> {
+    /// The byte range of the span in `contents`, for slicing.
+    #[inline]
+    pub fn byte_range(&self) -> std::ops::Range<usize> {
+        self.start.offset..self.end.offset
+    }
+
+    /// The length of the span in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end.offset - self.start.offset
+    }
+
+    /// Whether the span is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start.offset == self.end.offset
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate tempfile;
 
     use self::tempfile::NamedTempFile;
-    use super::{Position, SourceFile, Span};
+    use super::{AddError, Position, PositionId, SourceFile, Span};
     use std::io::Write;
 
     #[test]
@@ -185,10 +921,12 @@ mod tests {
         )
     }
 
-    fn test_files<'a>(
+    type SpanTest = ((usize, usize), (usize, usize, usize), (usize, usize, usize));
+
+    fn test_files(
         files: &[impl AsRef<str>],
         offset_tests: &[(usize, (usize, usize, usize))],
-        offset_span_tests: &[((usize, usize), (usize, usize, usize), (usize, usize, usize))],
+        offset_span_tests: &[SpanTest],
     ) {
         let mut sourcefile = SourceFile::default();
         let mut file_handles = Vec::new(); // don't clean me up please
@@ -202,7 +940,7 @@ mod tests {
         for &(offset, (file_idx, line, col)) in offset_tests {
             let filename = format!("{}", file_handles[file_idx].path().display());
             let pos = sourcefile.resolve_offset(offset);
-            assert_eq!(pos.unwrap(), Position::new(&filename, line, col));
+            assert_eq!(pos.unwrap(), Position::new(&filename, line, col, offset));
         }
 
         for &(
@@ -216,8 +954,8 @@ mod tests {
             assert_eq!(
                 sourcefile.resolve_offset_span(start, end).unwrap(),
                 Span {
-                    start: Position::new(&start_filename, line_start, col_start),
-                    end: Position::new(&end_filename, line_end, col_end),
+                    start: Position::new(&start_filename, line_start, col_start, start),
+                    end: Position::new(&end_filename, line_end, col_end, end),
                 }
             );
         }
@@ -227,6 +965,316 @@ mod tests {
     fn test_raw() {
         let mut sourcefile = SourceFile::new();
         sourcefile.add_file_raw("test", " ");
-        assert_eq!(*sourcefile.line_lengths.last().unwrap(), 1);
+        assert_eq!(sourcefile.line_len(0), 1);
+    }
+
+    #[test]
+    fn from_raw_files() {
+        let sourcefile = SourceFile::from_raw_files(vec![("a", "one\n"), ("b", "two\n")]);
+        let mut expected = SourceFile::new();
+        expected.add_file_raw("a", "one\n");
+        expected.add_file_raw("b", "two\n");
+        assert_eq!(sourcefile.contents, expected.contents);
+        assert_eq!(sourcefile.resolve_offset(5), expected.resolve_offset(5));
+        assert_eq!(sourcefile.files().count(), 2);
+    }
+
+    #[test]
+    fn span_range() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo\n");
+        let span = sourcefile.resolve_offset_span(2, 6).unwrap();
+        assert_eq!(span.byte_range(), 2..6);
+        assert_eq!(&sourcefile.contents[span.byte_range()], "e\ntw");
+        assert_eq!(span.len(), 4);
+        assert!(!span.is_empty());
+        assert!(sourcefile.resolve_offset_span(5, 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn position_ids() {
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file_raw("a", "one\n");
+        let b = sourcefile.add_file_raw("b", "two\nthree\n");
+        let id = sourcefile.resolve_offset_id(9).unwrap();
+        assert_eq!(
+            id,
+            PositionId {
+                file: b,
+                line: 1,
+                col: 1,
+                offset: 9
+            }
+        );
+        assert_eq!(
+            sourcefile.position(id),
+            sourcefile.resolve_offset(9).unwrap()
+        );
+
+        let (start, end) = sourcefile.resolve_offset_span_ids(2, 9).unwrap();
+        assert_eq!((start.file, start.col, end.file, end.line), (a, 2, b, 1));
+        assert_eq!(sourcefile.resolve_offset_span_ids(9, 2), None);
+    }
+
+    #[test]
+    fn empty_file_is_recorded() {
+        let mut sourcefile = SourceFile::new();
+        let empty = sourcefile.add_file_raw("empty", "");
+        let other = sourcefile.add_file_raw("other", "x\n");
+        assert_eq!(sourcefile.file_name(empty), "empty");
+        assert_eq!(
+            sourcefile.resolve_offset(0).unwrap(),
+            Position::new("other", 0, 0, 0)
+        );
+        assert_ne!(empty, other);
+        assert_eq!(sourcefile.line_count(empty), 0);
+    }
+
+    #[test]
+    fn two_level_index() {
+        let corpus = ::Corpus::generate(&::CorpusConfig {
+            files: 40,
+            ..Default::default()
+        });
+        let mut sourcefile = corpus.source_file();
+        sourcefile.add_file_raw("empty", "");
+        sourcefile.add_file_raw("last", "x");
+        for offset in 0..=sourcefile.contents.len() {
+            let flat = sourcefile
+                .locate_line(offset)
+                .map(|(line_idx, line_start)| {
+                    (line_idx, line_start, sourcefile.locate_file(line_idx).0)
+                });
+            assert_eq!(sourcefile.locate_two_level(offset), flat);
+        }
+    }
+
+    #[test]
+    fn resolve_many_files() {
+        let mut sourcefile = SourceFile::new();
+        for idx in 0..100 {
+            sourcefile.add_file_raw(idx, format!("{}\nx\n", idx));
+            sourcefile.add_file_raw("empty", "");
+        }
+        let mut offset = 0;
+        for idx in 0..100 {
+            let name = idx.to_string();
+            let start = sourcefile.resolve_offset(offset).unwrap();
+            assert_eq!(start, Position::new(&name, 0, 0, offset));
+            let x = sourcefile.resolve_offset(offset + name.len() + 1).unwrap();
+            assert_eq!((x.filename, x.line, x.col), (&*name, 1, 0));
+            offset += name.len() + 3;
+        }
+        assert!(sourcefile.resolve_offset(offset).is_none());
+    }
+
+    #[test]
+    fn span_matches_points() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo\n");
+        sourcefile.add_file_raw("empty", "");
+        sourcefile.add_file_raw("b", "three\nfour");
+        let len = sourcefile.contents.len();
+        for start in 0..len {
+            for end in start..len {
+                let span = sourcefile.resolve_offset_span(start, end).unwrap();
+                assert_eq!(span.start, sourcefile.resolve_offset(start).unwrap());
+                assert_eq!(span.end, sourcefile.resolve_offset(end).unwrap());
+            }
+        }
+        assert!(sourcefile.resolve_offset_span(0, len).is_none());
+    }
+
+    #[test]
+    fn offset_of() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\n");
+        sourcefile.add_file_raw("b", "twö\r\nthree");
+        for offset in [0, 4, 6, 10, 14] {
+            let pos = sourcefile.resolve_offset(offset).unwrap();
+            assert_eq!(
+                sourcefile.offset_of(pos.filename, pos.line, pos.col),
+                Some(offset)
+            );
+        }
+        assert_eq!(sourcefile.offset_of("b", 0, 4), Some(8));
+        assert_eq!(sourcefile.offset_of("b", 0, 3), None);
+        assert_eq!(sourcefile.offset_of("b", 0, 5), None);
+        assert_eq!(sourcefile.offset_of("b", 2, 0), None);
+        assert_eq!(sourcefile.offset_of("c", 0, 0), None);
+    }
+
+    #[test]
+    fn file_span() {
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file_raw("a", "one\n");
+        let empty = sourcefile.add_file_raw("empty", "");
+        let b = sourcefile.add_file_raw("b", "two");
+        assert_eq!(sourcefile.file_span(a), 0..4);
+        assert_eq!(sourcefile.file_span(empty), 4..4);
+        assert_eq!(&sourcefile.contents[sourcefile.file_span(b)], "two");
+    }
+
+    #[test]
+    fn file_contents() {
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file_raw("a", "one\n");
+        let empty = sourcefile.add_file_raw("empty", "");
+        let b = sourcefile.add_file_raw("b", "two");
+        assert_eq!(sourcefile.file_contents(a), "one\n");
+        assert_eq!(sourcefile.file_contents(empty), "");
+        assert_eq!(sourcefile.file_contents(b), "two");
+    }
+
+    #[test]
+    fn line_start() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo\n");
+        let b = sourcefile.add_file_raw("b", "three\r\nfour");
+        assert_eq!(sourcefile.line_start(b, 0), Some(8));
+        assert_eq!(sourcefile.line_start(b, 1), Some(15));
+        assert_eq!(sourcefile.line_start(b, 2), None);
+    }
+
+    #[test]
+    fn span_text() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "é\n");
+        sourcefile.add_file_raw("b", "xyz");
+        assert_eq!(sourcefile.span_text(2, 5), Some("\nxy"));
+        assert_eq!(sourcefile.span_text(6, 6), Some(""));
+        assert_eq!(sourcefile.span_text(1, 3), None);
+        assert_eq!(sourcefile.span_text(3, 2), None);
+        assert_eq!(sourcefile.span_text(3, 7), None);
+    }
+
+    #[test]
+    fn line_range() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo");
+        sourcefile.add_file_raw("b", "three\r\n");
+        assert_eq!(sourcefile.line_range(0), Some(0..4));
+        assert_eq!(sourcefile.line_range(3), Some(0..4));
+        assert_eq!(sourcefile.line_range(6), Some(4..7));
+        assert_eq!(sourcefile.line_range(13), Some(7..14));
+        assert_eq!(sourcefile.line_range(14), None);
+    }
+
+    #[test]
+    fn try_add_empty_file() {
+        let mut sourcefile = SourceFile::new();
+        assert_eq!(
+            sourcefile.try_add_file_raw("empty", ""),
+            Err(AddError::Empty)
+        );
+        assert!(sourcefile.files().next().is_none());
+        let id = sourcefile.try_add_file_raw("other", "x\n").unwrap();
+        assert_eq!(sourcefile.file_name(id), "other");
+    }
+
+    #[test]
+    fn char_counts() {
+        for &tracked in &[true, false] {
+            let mut sourcefile = if tracked {
+                SourceFile::with_char_counts()
+            } else {
+                SourceFile::new()
+            };
+            sourcefile.add_file_raw("a", "h\u{e9}llo\n");
+            let b = sourcefile.add_file_raw("b", "\u{1f600}\r\n\u{3b1}\u{3b2}");
+            assert_eq!(sourcefile.char_len(), sourcefile.contents.chars().count());
+            assert_eq!(sourcefile.line_char_len(b, 0), Some(3));
+            assert_eq!(sourcefile.line_char_len(b, 1), Some(2));
+            assert_eq!(sourcefile.line_char_len(b, 2), None);
+        }
+    }
+
+    #[test]
+    fn display_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "x\n").unwrap();
+
+        let mut sourcefile = SourceFile::new();
+        let raw = sourcefile.add_file_raw("raw", "y\n");
+        let id = sourcefile.add_file(&path).unwrap();
+        assert_eq!(sourcefile.file_name(id), path.display().to_string());
+
+        sourcefile.set_display_base(dir.path()).unwrap();
+        assert_eq!(sourcefile.file_name(id), "lib.rs");
+        assert_eq!(sourcefile.resolve_offset(2).unwrap().filename, "lib.rs");
+        assert_eq!(sourcefile.file_path(id), Some(path.as_path()));
+        assert_eq!(sourcefile.file_name(raw), "raw");
+        assert_eq!(sourcefile.file_path(raw), None);
+
+        let later = sourcefile.add_file(&path).unwrap();
+        assert_eq!(sourcefile.file_name(later), "lib.rs");
+        sourcefile.set_display_base("/nonexistent/base").unwrap();
+        assert_eq!(sourcefile.file_name(id), path.display().to_string());
+    }
+
+    #[test]
+    fn clear() {
+        let mut sourcefile = SourceFile::with_char_counts();
+        sourcefile.add_file_raw("a", "first\nfile\n");
+        let capacity = sourcefile.contents.capacity();
+        sourcefile.clear();
+        assert!(sourcefile.contents.is_empty());
+        assert!(sourcefile.resolve_offset(0).is_none());
+        assert_eq!(sourcefile.contents.capacity(), capacity);
+
+        let id = sourcefile.add_file_raw("b", "second\n");
+        assert_eq!(
+            sourcefile.resolve_offset(1).unwrap(),
+            Position::new("b", 0, 1, 1)
+        );
+        assert_eq!(sourcefile.line_char_len(id, 0), Some(7));
+        assert_eq!(sourcefile.char_len(), 7);
+    }
+
+    #[test]
+    fn with_file() {
+        let mut sourcefile = SourceFile::with_char_counts();
+        sourcefile.add_file_raw("fixture", "shared\n");
+        let expected = sourcefile.clone();
+
+        let pos = sourcefile.with_file("case", "extra\nlines\n", |sourcefile, id| {
+            sourcefile.add_file_raw("nested", "more\n");
+            assert_eq!(sourcefile.line_count(id), 2);
+            sourcefile.resolve_offset(8).unwrap().filename.to_owned()
+        });
+        assert_eq!(pos, "case");
+        assert_eq!(sourcefile.contents, expected.contents);
+        assert_eq!(sourcefile.files().count(), 1);
+        assert_eq!(sourcefile.char_len(), expected.char_len());
+        assert!(sourcefile.resolve_offset(8).is_none());
+        assert_eq!(sourcefile.verify_index(), Ok(()));
+    }
+
+    #[test]
+    fn line_count() {
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file_raw("a", "one\ntwo\n");
+        let b = sourcefile.add_file_raw("b", "one\ntwo\nthree");
+        assert_eq!(sourcefile.line_count(a), 2);
+        assert_eq!(sourcefile.line_count(b), 3);
+    }
+
+    #[test]
+    fn languages() {
+        let mut sourcefile = SourceFile::new();
+        let mut file = tempfile::Builder::new().suffix(".RS").tempfile().unwrap();
+        writeln!(file, "fn main() {{}}").unwrap();
+        let rust = sourcefile.add_file(file.path()).unwrap();
+        let query = sourcefile.add_file_raw("query", "SELECT 1;\n");
+        let other = sourcefile.add_file_raw("other", "SELECT 2;\n");
+        assert_eq!(sourcefile.file_language(rust), Some("rs"));
+        assert_eq!(sourcefile.file_language(query), None);
+
+        sourcefile.set_file_language(query, "sql");
+        sourcefile.set_file_language(other, "sql");
+        let sql: Vec<_> = sourcefile.files_with_language("sql").collect();
+        assert_eq!(sql, vec![query, other]);
+        assert_eq!(sourcefile.files_with_language("rs").count(), 1);
     }
 }
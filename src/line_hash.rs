@@ -0,0 +1,91 @@
+//! Finding lines that are repeated across the source.
+
+use std::collections::HashMap;
+
+use lines::trim_line_ending;
+use {Position, SourceFile};
+
+/// An index from line text to every place that line occurs, see `SourceFile::line_hash_index`.
+#[derive(Debug, Clone)]
+pub struct LineHashIndex<'a> {
+    sourcefile: &'a SourceFile,
+    /// The start offset of every occurrence of each line, in source order.
+    lines: HashMap<&'a str, Vec<usize>>,
+}
+
+impl<'a> LineHashIndex<'a> {
+    /// The other places where the line containing `offset` occurs, in source order.
+    pub fn duplicates_of(&self, offset: usize) -> Vec<Position<'a>> {
+        let (line_idx, start) = match self.sourcefile.locate_line(offset) {
+            Some(found) => found,
+            None => return Vec::new(),
+        };
+        let text = self.line_text(line_idx, start);
+        match self.lines.get(text) {
+            Some(starts) => starts
+                .iter()
+                .filter(|&&other| other != start)
+                .filter_map(|&other| self.sourcefile.resolve_offset(other))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Every line that occurs more than once, with the start offsets of its occurrences.
+    pub fn duplicate_groups<'s>(&'s self) -> impl Iterator<Item = (&'a str, &'s [usize])> + 's {
+        self.lines
+            .iter()
+            .filter(|(_, starts)| starts.len() > 1)
+            .map(|(&text, starts)| (text, starts.as_slice()))
+    }
+
+    fn line_text(&self, line_idx: usize, start: usize) -> &'a str {
+        let sourcefile = self.sourcefile;
+        trim_line_ending(&sourcefile.contents[start..sourcefile.line_ends[line_idx]])
+    }
+}
+
+impl SourceFile {
+    /// Index every line by its text (without its line ending), for finding duplicated lines.
+    ///
+    /// Lines that are empty or only whitespace are left out, since they would match everywhere.
+    pub fn line_hash_index<'a>(&'a self) -> LineHashIndex<'a> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("line_hash_index", lines = self.line_ends.len()).entered();
+        let mut index = LineHashIndex {
+            sourcefile: self,
+            lines: HashMap::new(),
+        };
+        let mut start = 0;
+        for line_idx in 0..self.line_ends.len() {
+            let text = index.line_text(line_idx, start);
+            if !text.trim().is_empty() {
+                index.lines.entry(text).or_default().push(start);
+            }
+            start = self.line_ends[line_idx];
+        }
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn duplicates() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "[section]\nkey = 1\n\n");
+        sourcefile.add_file_raw("b", "other\r\nkey = 1\n\n");
+        let index = sourcefile.line_hash_index();
+
+        let dups = index.duplicates_of(12);
+        assert_eq!(dups.len(), 1);
+        assert_eq!((dups[0].filename, dups[0].line), ("b", 1));
+        assert!(index.duplicates_of(0).is_empty());
+        assert!(index.duplicates_of(18).is_empty());
+
+        let groups: Vec<_> = index.duplicate_groups().collect();
+        assert_eq!(groups, vec![("key = 1", &[10, 26][..])]);
+    }
+}
@@ -0,0 +1,186 @@
+//! Sets of lines, for quickly asking whether a line is covered by any of a batch of spans.
+
+use {FileId, SourceFile};
+
+/// A set of `(FileId, line)` pairs, stored as one bit per line. See `SourceFile::line_set`.
+///
+/// Sets are only meaningful for the `SourceFile` they were built from, and combining sets built
+/// from different `SourceFile`s gives nonsense.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct LineSet {
+    /// The global index of the first line of each file, plus the total number of lines.
+    file_starts: Vec<usize>,
+    /// One bit for each global line index.
+    bits: Vec<u64>,
+}
+
+impl LineSet {
+    fn empty(file_starts: Vec<usize>) -> Self {
+        let lines = *file_starts.last().unwrap_or(&0);
+        LineSet {
+            file_starts,
+            bits: vec![0; lines.div_ceil(64)],
+        }
+    }
+
+    /// The global line index of a line of a file, if it has that line.
+    fn global_line(&self, file: FileId, line: usize) -> Option<usize> {
+        let start = *self.file_starts.get(file.0)?;
+        let end = *self.file_starts.get(file.0 + 1)?;
+        if start + line < end {
+            Some(start + line)
+        } else {
+            None
+        }
+    }
+
+    fn set(&mut self, line_idx: usize) {
+        self.bits[line_idx / 64] |= 1 << (line_idx % 64);
+    }
+
+    /// Whether a 0-indexed line of a file is in the set.
+    #[inline]
+    pub fn contains(&self, file: FileId, line: usize) -> bool {
+        match self.global_line(file, line) {
+            Some(idx) => self.bits[idx / 64] & (1 << (idx % 64)) != 0,
+            None => false,
+        }
+    }
+
+    /// Add a line to the set, returning whether it was newly added. Lines the file doesn't have
+    /// are ignored.
+    pub fn insert(&mut self, file: FileId, line: usize) -> bool {
+        match self.global_line(file, line) {
+            Some(idx) => {
+                let was_set = self.bits[idx / 64] & (1 << (idx % 64)) != 0;
+                self.set(idx);
+                !was_set
+            }
+            None => false,
+        }
+    }
+
+    /// The number of lines in the set.
+    pub fn len(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Whether the set has no lines.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// The lines in either set.
+    pub fn union(&self, other: &LineSet) -> LineSet {
+        self.combine(other, |a, b| a | b)
+    }
+
+    /// The lines in both sets.
+    pub fn intersection(&self, other: &LineSet) -> LineSet {
+        self.combine(other, |a, b| a & b)
+    }
+
+    fn combine<F: Fn(u64, u64) -> u64>(&self, other: &LineSet, op: F) -> LineSet {
+        debug_assert_eq!(
+            self.file_starts, other.file_starts,
+            "line sets from different sourcefiles"
+        );
+        LineSet {
+            file_starts: self.file_starts.clone(),
+            bits: self
+                .bits
+                .iter()
+                .zip(&other.bits)
+                .map(|(&a, &b)| op(a, b))
+                .collect(),
+        }
+    }
+
+    /// Iterate over the lines in the set, in order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (FileId, usize)> + 'a {
+        let mut file = 0;
+        self.bits
+            .iter()
+            .enumerate()
+            .flat_map(|(word_idx, &word)| {
+                (0..64)
+                    .filter(move |bit| word & (1 << bit) != 0)
+                    .map(move |bit| word_idx * 64 + bit)
+            })
+            .map(move |idx| {
+                while self.file_starts[file + 1] <= idx {
+                    file += 1;
+                }
+                (FileId(file), idx - self.file_starts[file])
+            })
+    }
+}
+
+impl SourceFile {
+    /// Build the set of lines touched by any of the given `(start, end)` spans of `contents`.
+    ///
+    /// An empty span touches the line it is on. Spans that don't resolve are skipped.
+    pub fn line_set(&self, spans: &[(usize, usize)]) -> LineSet {
+        let mut file_starts = Vec::with_capacity(self.file_line_ends.len() + 1);
+        file_starts.push(0);
+        file_starts.extend_from_slice(&self.file_line_ends);
+
+        let mut set = LineSet::empty(file_starts);
+        for &(start, end) in spans {
+            if end < start {
+                continue;
+            }
+            let (first, _) = match self.locate_line(start) {
+                Some(found) => found,
+                None => continue,
+            };
+            let last = if end > start {
+                match self.locate_line(end - 1) {
+                    Some((last, _)) => last,
+                    None => continue,
+                }
+            } else {
+                first
+            };
+            for line_idx in first..=last {
+                set.set(line_idx);
+            }
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn line_set() {
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file_raw("a", "a\nb\nc\n");
+        let b = sourcefile.add_file_raw("b", "d\ne\n");
+        // "b\nc" in a, the empty span at the start of "e", and an unresolvable span.
+        let first = sourcefile.line_set(&[(2, 5), (8, 8), (100, 101)]);
+        assert!(!first.contains(a, 0));
+        assert!(first.contains(a, 1));
+        assert!(first.contains(a, 2));
+        assert!(!first.contains(b, 0));
+        assert!(first.contains(b, 1));
+        assert!(!first.contains(b, 2));
+        assert_eq!(first.len(), 3);
+        assert_eq!(first.iter().collect::<Vec<_>>(), [(a, 1), (a, 2), (b, 1)]);
+
+        let mut second = sourcefile.line_set(&[(0, 1)]);
+        assert!(second.insert(a, 2));
+        assert!(!second.insert(a, 2));
+        assert_eq!(
+            first.intersection(&second).iter().collect::<Vec<_>>(),
+            [(a, 2)]
+        );
+        assert_eq!(first.union(&second).len(), 4);
+        assert!(sourcefile.line_set(&[]).is_empty());
+    }
+}
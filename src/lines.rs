@@ -0,0 +1,329 @@
+//! Line-oriented views of the source text.
+
+use std::cell::OnceCell;
+use std::ops::Range;
+
+use {Error, FileId, Position, SourceFile};
+
+/// A run of whole lines from one file, as yielded by `SourceFile::chunks_by_lines`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct LineChunk<'a> {
+    /// The byte range of the chunk in `contents`.
+    pub range: Range<usize>,
+    /// The text of the chunk, including line endings.
+    pub text: &'a str,
+    /// The position of the first byte of the chunk.
+    pub start: Position<'a>,
+    /// The number of lines in the chunk.
+    pub lines: usize,
+}
+
+/// Where a line is, as passed to and returned from `SourceFile::grep_lines`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct LineInfo<'a> {
+    /// The file the line is in.
+    pub file: FileId,
+    /// The position of the start of the line.
+    pub start: Position<'a>,
+    /// The byte range of the line in `contents`, excluding its line ending.
+    pub range: Range<usize>,
+}
+
+/// An iterator over chunks of whole lines, see `SourceFile::chunks_by_lines`.
+#[derive(Debug, Clone)]
+pub struct LineChunks<'a> {
+    sourcefile: &'a SourceFile,
+    chunk_lines: usize,
+    file_idx: usize,
+    /// The next line to yield, local to the current file.
+    line: usize,
+    /// The global index of the next line to yield.
+    line_idx: usize,
+    offset: usize,
+}
+
+impl<'a> Iterator for LineChunks<'a> {
+    type Item = LineChunk<'a>;
+
+    fn next(&mut self) -> Option<LineChunk<'a>> {
+        let sourcefile = self.sourcefile;
+        // Skip past finished (or empty) files.
+        while self.line_idx >= *sourcefile.file_line_ends.get(self.file_idx)? {
+            self.file_idx += 1;
+            self.line = 0;
+        }
+        let remaining = sourcefile.file_line_ends[self.file_idx] - self.line_idx;
+        let lines = remaining.min(self.chunk_lines);
+        let range = self.offset..sourcefile.line_ends[self.line_idx + lines - 1];
+        let chunk = LineChunk {
+            text: &sourcefile.contents[range.clone()],
+            range,
+            start: Position::new(
+                &sourcefile.file_names[self.file_idx],
+                self.line,
+                0,
+                self.offset,
+            ),
+            lines,
+        };
+        self.line += lines;
+        self.line_idx += lines;
+        self.offset = chunk.range.end;
+        Some(chunk)
+    }
+}
+
+/// An iterator over the lines of a file, see `SourceFile::lines`.
+#[derive(Debug, Clone)]
+pub struct FileLines<'a> {
+    sourcefile: &'a SourceFile,
+    /// The global index of the file's first line.
+    first_line: usize,
+    /// The global indices of the lines still to yield.
+    remaining: Range<usize>,
+}
+
+impl<'a> Iterator for FileLines<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        let line_idx = self.remaining.next()?;
+        let sourcefile = self.sourcefile;
+        let text =
+            &sourcefile.contents[sourcefile.line_offset(line_idx)..sourcefile.line_ends[line_idx]];
+        Some((line_idx - self.first_line, trim_line_ending(text)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.remaining.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for FileLines<'a> {}
+
+/// A cache of the line tables of the files in a `SourceFile`, see `SourceFile::line_tables`.
+///
+/// Each file's table is built the first time it is asked for, and kept for as long as the
+/// cache.
+#[derive(Debug, Clone)]
+pub struct LineTables<'a> {
+    sourcefile: &'a SourceFile,
+    tables: Vec<OnceCell<Vec<&'a str>>>,
+}
+
+impl<'a> LineTables<'a> {
+    /// The text of each line of a file, without line endings, as from `line_table`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by the `SourceFile`.
+    pub fn lines_of_str(&self, id: FileId) -> &[&'a str] {
+        self.tables[id.0].get_or_init(|| self.sourcefile.line_table(id))
+    }
+
+    /// Like `lines_of_str`, but returning an error rather than panicking for an unknown file.
+    pub fn try_lines_of_str(&self, id: FileId) -> Result<&[&'a str], Error> {
+        if !self.sourcefile.contains_file(id) {
+            return Err(Error::UnknownFile(id));
+        }
+        Ok(self.lines_of_str(id))
+    }
+}
+
+impl SourceFile {
+    /// Iterate over the 0-indexed line numbers and text of the lines of a file, without line
+    /// endings.
+    ///
+    /// Like `line_table`, this uses the recorded line boundaries rather than searching the text
+    /// for newlines.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn lines<'a>(&'a self, id: FileId) -> FileLines<'a> {
+        let remaining = self.file_line_indices(id);
+        FileLines {
+            sourcefile: self,
+            first_line: remaining.start,
+            remaining,
+        }
+    }
+
+    /// Build a table of the text of each line of a file, without line endings.
+    ///
+    /// The slices point into `contents`. Renderers and linters that look at many lines of one
+    /// file can keep the table around instead of finding line boundaries again for each line,
+    /// or use `line_tables` to share one table per file.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn line_table(&self, id: FileId) -> Vec<&str> {
+        self.file_line_indices(id)
+            .map(|line_idx| {
+                trim_line_ending(
+                    &self.contents[self.line_offset(line_idx)..self.line_ends[line_idx]],
+                )
+            })
+            .collect()
+    }
+
+    /// A cache lending out the `line_table` of each file, building each at most once.
+    pub fn line_tables<'a>(&'a self) -> LineTables<'a> {
+        LineTables {
+            sourcefile: self,
+            tables: (0..self.file_names.len())
+                .map(|_| OnceCell::new())
+                .collect(),
+        }
+    }
+
+    /// The text of a 0-indexed line of a file, without its line ending, or `None` if the file
+    /// has no such line.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn line(&self, id: FileId, line: usize) -> Option<&str> {
+        let start = self.line_start(id, line)?;
+        let line_idx = self.file_line_indices(id).start + line;
+        Some(trim_line_ending(
+            &self.contents[start..self.line_ends[line_idx]],
+        ))
+    }
+
+    /// Collect the lines for which `predicate` returns true, in source order.
+    ///
+    /// The predicate is given each line's location and its text without the line ending.
+    pub fn grep_lines<'a, F>(&'a self, mut predicate: F) -> Vec<(LineInfo<'a>, &'a str)>
+    where
+        F: FnMut(&LineInfo<'a>, &'a str) -> bool,
+    {
+        let mut matches = Vec::new();
+        for file_idx in 0..self.file_names.len() {
+            let lines = self.file_line_indices(FileId(file_idx));
+            for (line, line_idx) in lines.enumerate() {
+                let offset = self.line_offset(line_idx);
+                let text = trim_line_ending(&self.contents[offset..self.line_ends[line_idx]]);
+                let info = LineInfo {
+                    file: FileId(file_idx),
+                    start: Position::new(&self.file_names[file_idx], line, 0, offset),
+                    range: offset..offset + text.len(),
+                };
+                if predicate(&info, text) {
+                    matches.push((info, text));
+                }
+            }
+        }
+        matches
+    }
+
+    /// Split `contents` into chunks of up to `n` whole lines, for dividing up work.
+    ///
+    /// Chunks never split a line or cross from one file into the next, so the last chunk of each
+    /// file may be shorter.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `n` is 0.
+    pub fn chunks_by_lines<'a>(&'a self, n: usize) -> LineChunks<'a> {
+        assert!(n > 0, "chunks must contain at least one line");
+        LineChunks {
+            sourcefile: self,
+            chunk_lines: n,
+            file_idx: 0,
+            line: 0,
+            line_idx: 0,
+            offset: 0,
+        }
+    }
+}
+
+/// Strip a trailing `\n` or `\r\n` from a line.
+pub(crate) fn trim_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use {Position, SourceFile};
+
+    #[test]
+    fn line_table() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "skip\n");
+        let b = sourcefile.add_file_raw("b", "one\r\n\ntwo\rthree");
+        assert_eq!(sourcefile.line_table(b), vec!["one", "", "two\rthree"]);
+
+        let tables = sourcefile.line_tables();
+        let lines = tables.lines_of_str(b);
+        assert_eq!(lines, &["one", "", "two\rthree"]);
+        // The second lookup lends out the same table.
+        assert!(std::ptr::eq(lines, tables.lines_of_str(b)));
+    }
+
+    #[test]
+    fn lines() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "skip\n");
+        let b = sourcefile.add_file_raw("b", "one\r\n\ntwo");
+        let empty = sourcefile.add_file_raw("empty", "");
+        let lines: Vec<_> = sourcefile.lines(b).collect();
+        assert_eq!(lines, vec![(0, "one"), (1, ""), (2, "two")]);
+        assert_eq!(sourcefile.lines(b).len(), 3);
+        assert_eq!(sourcefile.lines(empty).next(), None);
+    }
+
+    #[test]
+    fn line() {
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file_raw("a", "skip\n");
+        let b = sourcefile.add_file_raw("b", "one\r\n\ntwo");
+        assert_eq!(sourcefile.line(a, 0), Some("skip"));
+        assert_eq!(sourcefile.line(b, 0), Some("one"));
+        assert_eq!(sourcefile.line(b, 1), Some(""));
+        assert_eq!(sourcefile.line(b, 2), Some("two"));
+        assert_eq!(sourcefile.line(b, 3), None);
+    }
+
+    #[test]
+    fn grep() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "fn a() {}\n// TODO: x\n");
+        let b = sourcefile.add_file_raw("b", "ok\r\n  // TODO: y\r\n");
+
+        let todos = sourcefile.grep_lines(|_, text| text.trim_start().starts_with("// TODO"));
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].1, "// TODO: x");
+        assert_eq!(todos[0].0.start, Position::new("a", 1, 0, 10));
+        let (ref info, text) = todos[1];
+        assert_eq!((info.file, info.start.line, text), (b, 1, "  // TODO: y"));
+        assert_eq!(&sourcefile.contents[info.range.clone()], text);
+
+        let in_b = sourcefile.grep_lines(|info, _| info.file == b);
+        assert_eq!(in_b.len(), 2);
+    }
+
+    #[test]
+    fn chunks() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "1\n2\n3\n");
+        sourcefile.add_file_raw("empty", "");
+        sourcefile.add_file_raw("b", "4\n5");
+
+        let chunks: Vec<_> = sourcefile.chunks_by_lines(2).collect();
+        let texts: Vec<_> = chunks.iter().map(|chunk| chunk.text).collect();
+        assert_eq!(texts, vec!["1\n2\n", "3\n", "4\n5"]);
+        assert_eq!(chunks[1].range, 4..6);
+        assert_eq!(chunks[1].start, Position::new("a", 2, 0, 4));
+        assert_eq!(chunks[2].start, Position::new("b", 0, 0, 6));
+        assert_eq!(chunks[2].lines, 2);
+        for chunk in &chunks {
+            assert_eq!(
+                sourcefile.resolve_offset(chunk.range.start),
+                Some(chunk.start)
+            );
+        }
+    }
+}
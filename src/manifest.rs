@@ -0,0 +1,197 @@
+//! Machine-readable descriptions of the concatenation, to ship alongside it.
+
+use std::ops::Range;
+use std::{error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use {FileId, SourceFile};
+
+/// A description of the files in a `SourceFile`, see `SourceFile::manifest`.
+///
+/// This can be written as JSON or TOML and read back, e.g. by a later stage of a build pipeline
+/// that checks the concatenated artifact it was given with `validate`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The files, in the order they were concatenated.
+    pub files: Vec<ManifestFile>,
+}
+
+/// One file of a `Manifest`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// The name the file was added under.
+    pub name: String,
+    /// The byte range of the file in `contents`.
+    pub bytes: Range<usize>,
+    /// The range of the file's lines, counted from 0 across the whole concatenation.
+    pub lines: Range<usize>,
+    /// The 64-bit FNV-1a hash of the file's text, as 16 lowercase hex digits.
+    pub hash: String,
+}
+
+/// Why a manifest couldn't be read, or doesn't describe a `SourceFile`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ManifestError {
+    /// The text isn't a JSON manifest.
+    Json(serde_json::Error),
+    /// The text isn't a TOML manifest.
+    Toml(toml::de::Error),
+    /// The manifest describes a different number of files.
+    FileCount {
+        /// The number of files in the manifest.
+        manifest: usize,
+        /// The number of files in the `SourceFile`.
+        actual: usize,
+    },
+    /// A file differs from its description.
+    Mismatch {
+        /// The index of the file in the manifest.
+        file: usize,
+        /// The field that differs: `name`, `bytes`, `lines` or `hash`.
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ManifestError::Json(ref err) => write!(f, "invalid JSON manifest: {}", err),
+            ManifestError::Toml(ref err) => write!(f, "invalid TOML manifest: {}", err),
+            ManifestError::FileCount { manifest, actual } => write!(
+                f,
+                "manifest lists {} files, but there are {}",
+                manifest, actual
+            ),
+            ManifestError::Mismatch { file, field } => {
+                write!(
+                    f,
+                    "file {} has a different {} from the manifest",
+                    file, field
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for ManifestError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ManifestError::Json(ref err) => Some(err),
+            ManifestError::Toml(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Manifest {
+    /// The manifest as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("manifests are always valid JSON")
+    }
+
+    /// The manifest as TOML, with a `[[files]]` table for each file.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("manifests are always valid TOML")
+    }
+
+    /// Read a manifest written by `to_json`.
+    pub fn from_json(text: &str) -> Result<Self, ManifestError> {
+        serde_json::from_str(text).map_err(ManifestError::Json)
+    }
+
+    /// Read a manifest written by `to_toml`.
+    pub fn from_toml(text: &str) -> Result<Self, ManifestError> {
+        toml::from_str(text).map_err(ManifestError::Toml)
+    }
+
+    /// Check that `sourcefile` has exactly the files this manifest describes, reporting the
+    /// first difference.
+    pub fn validate(&self, sourcefile: &SourceFile) -> Result<(), ManifestError> {
+        let actual = sourcefile.manifest();
+        if self.files.len() != actual.files.len() {
+            return Err(ManifestError::FileCount {
+                manifest: self.files.len(),
+                actual: actual.files.len(),
+            });
+        }
+        for (idx, (expected, actual)) in self.files.iter().zip(&actual.files).enumerate() {
+            let field = if expected.name != actual.name {
+                "name"
+            } else if expected.bytes != actual.bytes {
+                "bytes"
+            } else if expected.lines != actual.lines {
+                "lines"
+            } else if expected.hash != actual.hash {
+                "hash"
+            } else {
+                continue;
+            };
+            return Err(ManifestError::Mismatch { file: idx, field });
+        }
+        Ok(())
+    }
+}
+
+impl SourceFile {
+    /// Describe the files, in order, with where each is in `contents` and a hash of its text.
+    ///
+    /// This hashes the text of every file.
+    pub fn manifest(&self) -> Manifest {
+        let files = (0..self.file_names.len())
+            .map(|idx| {
+                let id = FileId(idx);
+                ManifestFile {
+                    name: self.file_names[idx].clone(),
+                    bytes: self.file_span(id),
+                    lines: self.file_line_indices(id),
+                    hash: format!("{:016x}", fnv1a(self.file_contents(id).as_bytes())),
+                }
+            })
+            .collect();
+        Manifest { files }
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`, which unlike `DefaultHasher` is the same everywhere.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fnv1a, Manifest, ManifestError};
+    use SourceFile;
+
+    #[test]
+    fn manifest_round_trip() {
+        let sourcefile = SourceFile::from_raw_files(vec![("a.js", "one\ntwo\n"), ("b.js", "x")]);
+        let manifest = sourcefile.manifest();
+        assert_eq!(manifest.files[1].bytes, 8..9);
+        assert_eq!(manifest.files[1].lines, 2..3);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+        let json = Manifest::from_json(&manifest.to_json()).unwrap();
+        let toml = Manifest::from_toml(&manifest.to_toml()).unwrap();
+        assert_eq!(json, manifest);
+        assert_eq!(toml, manifest);
+        json.validate(&sourcefile).unwrap();
+
+        let changed = SourceFile::from_raw_files(vec![("a.js", "one\nTWO\n"), ("b.js", "x")]);
+        match json.validate(&changed) {
+            Err(ManifestError::Mismatch { file: 0, field }) => assert_eq!(field, "hash"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match json.validate(&SourceFile::new()) {
+            Err(ManifestError::FileCount {
+                manifest: 2,
+                actual: 0,
+            }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(Manifest::from_json("{}").is_err());
+    }
+}
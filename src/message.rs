@@ -0,0 +1,176 @@
+//! Formatting messages that quote the source they are about.
+
+#[cfg(feature = "unicode-segmentation")]
+use unicode_segmentation::UnicodeSegmentation;
+
+use SourceFile;
+
+/// The most chars of source quoted by `{snippet}` before it is cut short.
+const MAX_SNIPPET_CHARS: usize = 40;
+
+impl SourceFile {
+    /// Expand the placeholders in `template` using the span `start..end`.
+    ///
+    ///  - `{span}` becomes the location of the start of the span, as `path:line:col` (1-indexed)
+    ///    or as described by `location` for option snippets.
+    ///  - `{snippet}` becomes the text of the span. Newlines and other control characters are
+    ///    escaped, and text longer than 40 chars is cut short with `…`.
+    ///  - `{{` and `}}` become `{` and `}`.
+    ///
+    /// Other text, including unknown placeholders, is copied unchanged. Returns `None` if the
+    /// span can't be resolved.
+    ///
+    /// ```
+    /// # use sourcefile::SourceFile;
+    /// let mut source = SourceFile::new();
+    /// source.add_file_raw("main.c", "int x = 1\n");
+    /// assert_eq!(
+    ///     source.format_message("{span}: expected `;` after `{snippet}`", 8, 9).unwrap(),
+    ///     "main.c:1:9: expected `;` after `1`"
+    /// );
+    /// ```
+    pub fn format_message(&self, template: &str, start: usize, end: usize) -> Option<String> {
+        let span = self.resolve_offset_span(start, end)?;
+        let text = self.contents.get(start..end)?;
+
+        let mut out = String::with_capacity(template.len() + text.len());
+        let mut rest = template;
+        while let Some(idx) = rest.find(['{', '}']) {
+            out.push_str(&rest[..idx]);
+            rest = &rest[idx..];
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                out.push_str(&rest[..1]);
+                rest = &rest[2..];
+            } else if rest.starts_with("{span}") {
+                out.push_str(&self.location(&span.start));
+                rest = &rest["{span}".len()..];
+            } else if rest.starts_with("{snippet}") {
+                quote(&mut out, text);
+                rest = &rest["{snippet}".len()..];
+            } else {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+        out.push_str(rest);
+        Some(out)
+    }
+
+    /// The text of the span `start..end` on one line, shortened to at most `max_len` chars of
+    /// source by replacing the middle with `…`.
+    ///
+    /// The start and end of the text are kept since they are usually the most telling parts of a
+    /// span. As with `{snippet}` in `format_message`, newlines and other control characters are
+    /// escaped. Returns `None` if `start..end` isn't a span of `contents`.
+    ///
+    /// With the `unicode-segmentation` feature, `max_len` counts grapheme clusters and cuts are
+    /// only made between them, so e.g. `e` followed by a combining accent is kept or dropped
+    /// whole. Without it, cuts are made between chars and can separate a combining mark from the
+    /// char before it.
+    pub fn excerpt(&self, start: usize, end: usize, max_len: usize) -> Option<String> {
+        let text = self.contents.get(start..end)?;
+        let cuts = cut_points(text);
+        let len = cuts.len() - 1;
+        let mut out = String::with_capacity(text.len().min(4 * max_len));
+        if len <= max_len {
+            escape_into(&mut out, text.chars());
+            return Some(out);
+        }
+        if max_len == 0 {
+            return Some(out);
+        }
+        // One unit of the budget goes on the ellipsis, and the start gets any odd one out.
+        let keep = max_len - 1;
+        let tail = keep / 2;
+        let head = keep - tail;
+        escape_into(&mut out, text[..cuts[head]].chars());
+        out.push('…');
+        escape_into(&mut out, text[cuts[len - tail]..].chars());
+        Some(out)
+    }
+}
+
+/// The offsets in `text` at which `excerpt` may cut it: the start of each grapheme cluster,
+/// then the end of `text`.
+#[cfg(feature = "unicode-segmentation")]
+fn cut_points(text: &str) -> Vec<usize> {
+    let starts = text.grapheme_indices(true).map(|(idx, _)| idx);
+    starts.chain(Some(text.len())).collect()
+}
+
+/// The offsets in `text` at which `excerpt` may cut it: the start of each char, then the end of
+/// `text`.
+#[cfg(not(feature = "unicode-segmentation"))]
+fn cut_points(text: &str) -> Vec<usize> {
+    let starts = text.char_indices().map(|(idx, _)| idx);
+    starts.chain(Some(text.len())).collect()
+}
+
+/// Push `chars` onto `out`, escaping control characters.
+fn escape_into<I: Iterator<Item = char>>(out: &mut String, chars: I) {
+    for c in chars {
+        if c.is_control() {
+            out.extend(c.escape_default());
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+/// Push `text` onto `out`, escaped and cut short if needed.
+fn quote(out: &mut String, text: &str) {
+    escape_into(out, text.chars().take(MAX_SNIPPET_CHARS));
+    if text.chars().nth(MAX_SNIPPET_CHARS).is_some() {
+        out.push('…');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn format_message() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "let s = \"x\ty\";\n");
+        sourcefile.add_file_raw("b", "z".repeat(50));
+
+        assert_eq!(
+            sourcefile
+                .format_message("{{{snippet}}} at {span} {unknown}", 8, 13)
+                .unwrap(),
+            "{\"x\\ty\"} at a:1:9 {unknown}"
+        );
+        let long = sourcefile.format_message("{snippet}", 15, 64).unwrap();
+        assert_eq!(long, format!("{}…", "z".repeat(40)));
+        assert!(sourcefile.format_message("{span}", 0, 100).is_none());
+    }
+
+    #[test]
+    fn excerpt() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "fn é() {\n    body\n}\n");
+        assert_eq!(sourcefile.excerpt(0, 9, 20).unwrap(), "fn é() {");
+        assert_eq!(
+            sourcefile.excerpt(0, 21, 100).unwrap(),
+            "fn é() {\\n    body\\n}\\n"
+        );
+        assert_eq!(sourcefile.excerpt(0, 21, 6).unwrap(), "fn …}\\n");
+        assert_eq!(sourcefile.excerpt(0, 9, 5).unwrap(), "fn… {");
+        assert_eq!(sourcefile.excerpt(0, 9, 1).unwrap(), "…");
+        assert_eq!(sourcefile.excerpt(0, 9, 0).unwrap(), "");
+        assert!(sourcefile.excerpt(0, 4, 10).is_none());
+    }
+
+    #[test]
+    fn excerpt_combining() {
+        let mut sourcefile = SourceFile::new();
+        // "e" and a combining acute accent, then "x" and a combining diaeresis.
+        sourcefile.add_file_raw("a", "ae\u{301}bcdx\u{308}y");
+        if cfg!(feature = "unicode-segmentation") {
+            assert_eq!(sourcefile.excerpt(0, 11, 5).unwrap(), "ae\u{301}…x\u{308}y");
+        } else {
+            assert_eq!(sourcefile.excerpt(0, 11, 5).unwrap(), "ae…\u{308}y");
+        }
+    }
+}
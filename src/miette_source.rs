@@ -0,0 +1,127 @@
+//! Attaching a `SourceFile` to `miette` diagnostics.
+
+use miette::{MietteError, MietteSpanContents, SourceCode, SourceSpan, SpanContents};
+
+use {FileId, SourceFile};
+
+/// Spans are offsets into `contents`. The snippets read for them are named after the file the
+/// span starts in, with line numbers counted from the start of that file, and with context
+/// lines only from that file.
+///
+/// As for `str`, a snippet starts at the span itself if no context lines are wanted before
+/// it, and ends at the end of the span if none are wanted after it.
+impl SourceCode for SourceFile {
+    fn read_span<'a>(
+        &'a self,
+        span: &SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        let start = span.offset();
+        let end = start
+            .checked_add(span.len())
+            .ok_or(MietteError::OutOfBounds)?;
+        if end > self.contents.len() {
+            return Err(MietteError::OutOfBounds);
+        }
+        // A span at the very end is on the last line.
+        let (start_line, start_line_offset, file_idx) = self
+            .locate(start)
+            .or_else(|| self.locate(start.checked_sub(1)?))
+            .ok_or(MietteError::OutOfBounds)?;
+        let end_line = match end.checked_sub(1) {
+            Some(last) if last > start => self.locate_line(last).map_or(start_line, |(idx, _)| idx),
+            _ => start_line,
+        };
+
+        let id = FileId(file_idx);
+        let lines = self.file_line_indices(id);
+        let first_line = start_line
+            .saturating_sub(context_lines_before)
+            .max(lines.start);
+        let last_line = (end_line + context_lines_after)
+            .min(lines.end - 1)
+            .max(end_line);
+        let (data_start, column) = if context_lines_before == 0 {
+            (start, start - start_line_offset)
+        } else {
+            (self.line_offset(first_line), 0)
+        };
+        let data_end = if context_lines_after == 0 {
+            end
+        } else {
+            self.line_ends[last_line]
+        };
+
+        let contents = MietteSpanContents::new_named(
+            self.file_name(id).to_owned(),
+            &self.contents.as_bytes()[data_start..data_end],
+            (data_start, data_end - data_start).into(),
+            first_line - lines.start,
+            column,
+            last_line - first_line + 1,
+        );
+        Ok(Box::new(match self.file_language(id) {
+            Some(language) => contents.with_language(language),
+            None => contents,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::{LabeledSpan, MietteDiagnostic, NarratableReportHandler, Report, SourceCode};
+
+    use SourceFile;
+
+    fn sourcefile() -> SourceFile {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo\n");
+        sourcefile.add_file_raw("b", "three\nfour\nfive\n");
+        sourcefile
+    }
+
+    #[test]
+    fn read_span() {
+        let sourcefile = sourcefile();
+        let contents = sourcefile.read_span(&(14, 4).into(), 1, 1).unwrap();
+        assert_eq!(contents.name(), Some("b"));
+        assert_eq!(contents.data(), b"three\nfour\nfive\n");
+        assert_eq!((contents.line(), contents.column()), (0, 0));
+        assert_eq!(contents.line_count(), 3);
+
+        // Context stops at the start of the file.
+        let contents = sourcefile.read_span(&(9, 2).into(), 5, 0).unwrap();
+        assert_eq!(contents.data(), b"thr");
+        let contents = sourcefile.read_span(&(16, 2).into(), 0, 0).unwrap();
+        assert_eq!(contents.data(), b"ur");
+        assert_eq!((contents.line(), contents.column()), (1, 2));
+
+        assert!(sourcefile.read_span(&(24, 0).into(), 1, 1).is_ok());
+        assert!(sourcefile.read_span(&(23, 2).into(), 1, 1).is_err());
+        assert!(sourcefile
+            .read_span(&(usize::MAX - 1, 5).into(), 0, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn report() {
+        let diagnostic =
+            MietteDiagnostic::new("bad number").with_label(LabeledSpan::at(14..18, "here"));
+        let report = Report::new(diagnostic).with_source_code(sourcefile());
+        let mut out = String::new();
+        NarratableReportHandler::new()
+            .render_report(&mut out, report.as_ref())
+            .unwrap();
+        assert!(
+            out.contains("Begin snippet for b starting at line 1"),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains("label at line 2, columns 1 to 4: here"),
+            "{}",
+            out
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Moving a position by chars or lines, as editors do.
+
+use {Position, SourceFile};
+
+impl SourceFile {
+    /// The position `n` chars after `position`, crossing line endings and the ends of files.
+    ///
+    /// A `\r\n` line ending is two chars. Returns `None` if `position` isn't on a char boundary,
+    /// or if there are fewer than `n` chars after it before the end of `contents`.
+    pub fn position_after<'a>(&'a self, position: &Position, n: usize) -> Option<Position<'a>> {
+        let rest = self.contents.get(position.offset..)?;
+        let (byte, _) = rest.char_indices().nth(n)?;
+        self.resolve_offset(position.offset + byte)
+    }
+
+    /// The position `n` chars before `position`, crossing line endings and the starts of files.
+    ///
+    /// Returns `None` if `position` isn't on a char boundary, or if there are fewer than `n`
+    /// chars before it.
+    pub fn position_before<'a>(&'a self, position: &Position, n: usize) -> Option<Position<'a>> {
+        if n == 0 {
+            return self.resolve_offset(position.offset);
+        }
+        let before = self.contents.get(..position.offset)?;
+        let (byte, _) = before.char_indices().rev().nth(n - 1)?;
+        self.resolve_offset(byte)
+    }
+
+    /// The position `n` lines below `position`, moving into later files past the end of its own.
+    ///
+    /// The column is kept where the new line is long enough, and otherwise moved back to the end
+    /// of its text, before the line ending. A column inside a char is moved back to its start.
+    /// Returns `None` if there are fewer than `n` lines below.
+    pub fn position_lines_after<'a>(
+        &'a self,
+        position: &Position,
+        n: usize,
+    ) -> Option<Position<'a>> {
+        let (line_idx, line_start) = self.locate_line(position.offset)?;
+        self.position_on_line(line_idx.checked_add(n)?, position.offset - line_start)
+    }
+
+    /// The position `n` lines above `position`, moving into earlier files past the start of its
+    /// own. The column is kept as for `position_lines_after`.
+    ///
+    /// Returns `None` if there are fewer than `n` lines above.
+    pub fn position_lines_before<'a>(
+        &'a self,
+        position: &Position,
+        n: usize,
+    ) -> Option<Position<'a>> {
+        let (line_idx, line_start) = self.locate_line(position.offset)?;
+        self.position_on_line(line_idx.checked_sub(n)?, position.offset - line_start)
+    }
+
+    /// The position at byte column `col` of a line given its global index, or as near before it
+    /// as the line's text allows.
+    fn position_on_line<'a>(&'a self, line_idx: usize, col: usize) -> Option<Position<'a>> {
+        let end = *self.line_ends.get(line_idx)?;
+        let start = self.line_offset(line_idx);
+        let text = self.contents[start..end].trim_end_matches(['\n', '\r']);
+        let mut col = col.min(text.len());
+        while !text.is_char_boundary(col) {
+            col -= 1;
+        }
+        self.resolve_offset(start + col)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn navigate() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "aé\r\nb\n");
+        sourcefile.add_file_raw("empty", "");
+        sourcefile.add_file_raw("b", "long line\n");
+
+        let start = sourcefile.resolve_offset(0).unwrap();
+        let after = sourcefile.position_after(&start, 4).unwrap();
+        assert_eq!((after.filename, after.line, after.col), ("a", 1, 0));
+        let next_file = sourcefile.position_after(&after, 2).unwrap();
+        assert_eq!((next_file.filename, next_file.offset), ("b", 7));
+        assert_eq!(sourcefile.position_before(&next_file, 6), Some(start));
+        assert_eq!(sourcefile.position_before(&next_file, 0), Some(next_file));
+        assert_eq!(sourcefile.position_before(&start, 1), None);
+        assert_eq!(sourcefile.position_after(&next_file, 10), None);
+        let e_middle = sourcefile.resolve_offset(2).unwrap();
+        assert_eq!(sourcefile.position_after(&e_middle, 1), None);
+
+        let down = sourcefile
+            .position_lines_after(&sourcefile.resolve_offset(3).unwrap(), 1)
+            .unwrap();
+        assert_eq!((down.line, down.col), (1, 1));
+        let across = sourcefile.position_lines_after(&down, 1).unwrap();
+        assert_eq!((across.filename, across.line, across.col), ("b", 0, 1));
+        let line_end = sourcefile.resolve_offset(15).unwrap();
+        let up = sourcefile.position_lines_before(&line_end, 2).unwrap();
+        // Column 8 is past the end of "aé".
+        assert_eq!((up.line, up.col), (0, 3));
+        let in_e = sourcefile
+            .position_lines_before(&sourcefile.resolve_offset(9).unwrap(), 2)
+            .unwrap();
+        assert_eq!((in_e.line, in_e.col), (0, 1));
+        assert_eq!(sourcefile.position_lines_before(&line_end, 3), None);
+        assert_eq!(sourcefile.position_lines_after(&line_end, 1), None);
+    }
+}
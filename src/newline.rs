@@ -0,0 +1,85 @@
+//! Normalizing line endings to `\n` as files are added.
+
+use origin::OriginMap;
+use SourceFile;
+
+impl SourceFile {
+    /// Whether to replace `\r\n` and lone `\r` line endings with `\n` as files are added. This is
+    /// off by default.
+    ///
+    /// Offsets into normalized text agree with tools that normalize, and `original_offset` maps
+    /// them back to the bytes the file was added with, a `\n` that replaced `\r\n` mapping to the
+    /// `\r`. Files already added are unaffected, as are option snippets and regions copied with
+    /// `duplicate_region`.
+    pub fn set_normalize_line_endings(&mut self, normalize: bool) {
+        self.normalize_line_endings = normalize;
+    }
+
+    /// Whether line endings are normalized as files are added, see
+    /// `set_normalize_line_endings`.
+    #[inline]
+    pub fn normalizes_line_endings(&self) -> bool {
+        self.normalize_line_endings
+    }
+}
+
+/// Replace `\r\n` and lone `\r` in `text` with `\n`, recording in `origins` how the result maps
+/// back to `text`, which starts `base` bytes into the original.
+pub(crate) fn normalize_newlines(text: &str, base: usize, origins: &mut OriginMap) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (idx, _) in text.match_indices('\r') {
+        normalized.push_str(&text[copied..idx]);
+        normalized.push('\n');
+        if text[idx + 1..].starts_with('\n') {
+            // Two bytes became one, so the offsets after it shift.
+            origins.push(normalized.len() - 1, base + idx, false);
+            copied = idx + 2;
+            origins.push(normalized.len(), base + copied, true);
+        } else {
+            copied = idx + 1;
+        }
+    }
+    normalized.push_str(&text[copied..]);
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn normalize_line_endings() {
+        let mut sourcefile = SourceFile::new();
+        let kept = sourcefile.add_file_raw("kept", "a\r\n");
+        sourcefile.set_normalize_line_endings(true);
+        sourcefile.set_strip_bom(true);
+        assert!(sourcefile.normalizes_line_endings());
+        let mixed = sourcefile.add_file_raw("mixed", "\u{FEFF}one\r\ntwo\rthree\r\r\nend");
+        assert_eq!(sourcefile.file_contents(kept), "a\r\n");
+        assert_eq!(sourcefile.file_contents(mixed), "one\ntwo\nthree\n\nend");
+        assert_eq!(sourcefile.line_count(mixed), 5);
+
+        let base = sourcefile.file_span(mixed).start;
+        let original = |offset| sourcefile.original_offset(base + offset).unwrap().1;
+        assert_eq!(original(3), 6);
+        assert_eq!(original(4), 8);
+        assert_eq!(original(8), 12);
+        assert_eq!(original(13), 17);
+        assert_eq!(original(14), 18);
+        assert_eq!(original(15), 20);
+        let end = sourcefile.resolve_offset(base + 15).unwrap();
+        assert_eq!((end.line, end.col), (4, 0));
+    }
+
+    #[test]
+    fn lossy_replacements_after_normalizing() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.set_normalize_line_endings(true);
+        let id = sourcefile
+            .add_reader_lossy("bad", &b"a\r\nb\xff\n"[..])
+            .unwrap();
+        assert_eq!(sourcefile.file_contents(id), "a\nb\u{fffd}\n");
+        assert_eq!(sourcefile.replaced_ranges(id), &[(3, 6)]);
+    }
+}
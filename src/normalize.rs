@@ -0,0 +1,95 @@
+//! Unicode normalization of files as they are added.
+
+use std::fmt;
+
+use unicode_normalization::char::{canonical_combining_class, compose};
+use unicode_normalization::UnicodeNormalization;
+
+use origin::OriginMap;
+use {FileId, SourceFile};
+
+impl SourceFile {
+    /// Like `add_file_raw`, but converts the contents to Unicode Normalization Form C first.
+    ///
+    /// Text arriving in NFD (e.g. from macOS) has more bytes and chars than the same text in NFC,
+    /// so positions computed by tools that normalize disagree with the original. Positions here
+    /// are into the normalized text, and `original_offset` maps them back to the bytes that were
+    /// passed in. The normalized text is then added as by `add_file_raw`, so byte order marks
+    /// and line endings are handled as set.
+    pub fn add_file_raw_nfc(&mut self, name: impl fmt::Display, contents: &str) -> FileId {
+        let mut normalized = String::with_capacity(contents.len());
+        let mut origins = OriginMap::default();
+        let mut flush = |start: usize, end: usize, normalized: &mut String| {
+            let run = &contents[start..end];
+            let stored = normalized.len();
+            normalized.extend(run.nfc());
+            origins.push(stored, start, &normalized[stored..] == run);
+        };
+
+        // Normalize in runs that can't affect each other: a new run starts at each starter that
+        // doesn't compose with what came before it. `last` is the run so far, composed as far as
+        // we can tell, so that e.g. Hangul jamo build up into one syllable.
+        let mut run_start = 0;
+        let mut last = None;
+        for (idx, c) in contents.char_indices() {
+            last = match last.map(|last| (last, compose(last, c))) {
+                Some((_, Some(composed))) => Some(composed),
+                Some((_, None)) if canonical_combining_class(c) == 0 => {
+                    flush(run_start, idx, &mut normalized);
+                    run_start = idx;
+                    Some(c)
+                }
+                Some((last, None)) => Some(last),
+                None => Some(c),
+            };
+        }
+        flush(run_start, contents.len(), &mut normalized);
+
+        // The byte order mark and line endings are starters that compose with nothing, so are
+        // left alone by NFC for `ingest_file` to find.
+        let id = self.ingest_file(name.to_string(), &normalized);
+        self.file_origins[id.0] = self.file_origins[id.0].compose(&origins);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn nfc() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("plain", "x\n");
+        // "café" and "한" in decomposed form.
+        let nfd = "cafe\u{301} = \u{1112}\u{1161}\u{11ab}!\n";
+        let id = sourcefile.add_file_raw_nfc("nfd", nfd);
+        let file = sourcefile.files().nth(1).unwrap();
+        assert_eq!(file.contents, "caf\u{e9} = \u{d55c}!\n");
+
+        let base = file.range.start;
+        let at = |needle: &str| base + file.contents.find(needle).unwrap();
+        let original = |offset| sourcefile.original_offset(offset).unwrap();
+        assert_eq!(original(at("c")), (id, 0));
+        assert_eq!(original(at("\u{e9}")), (id, 3));
+        assert_eq!(original(at(" =")), (id, nfd.find(" =").unwrap()));
+        assert_eq!(original(at("!")), (id, nfd.find('!').unwrap()));
+        assert_eq!(
+            sourcefile.original_offset(1),
+            Some((sourcefile.files().next().unwrap().id, 1))
+        );
+    }
+
+    #[test]
+    fn nfc_with_ingest_options() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.set_strip_bom(true);
+        sourcefile.set_normalize_line_endings(true);
+        let id = sourcefile.add_file_raw_nfc("nfd", "\u{FEFF}e\u{301}\r\nx");
+        assert_eq!(sourcefile.file_contents(id), "\u{e9}\nx");
+        let original = |offset| sourcefile.original_offset(offset).unwrap().1;
+        assert_eq!(original(0), 3);
+        assert_eq!(original(2), 6);
+        assert_eq!(original(3), 8);
+    }
+}
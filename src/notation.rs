@@ -0,0 +1,192 @@
+//! The conventional `path:line:col` textual notation for positions and spans.
+
+use std::str::FromStr;
+use std::{error, fmt};
+
+use {SourceFile, Span};
+
+/// A position or span written as `path:line:col` or `path:line:col-line:col`.
+///
+/// Lines and columns are 1-indexed, as is conventional for this notation, and columns count
+/// bytes, as in `Position`.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SpanNotation {
+    /// The name of the file.
+    pub path: String,
+    /// The 1-indexed line and column of the start.
+    pub start: (usize, usize),
+    /// The 1-indexed line and column of the end, if this is a span rather than a point.
+    pub end: Option<(usize, usize)>,
+}
+
+impl SpanNotation {
+    /// Describe a resolved span. A span that starts and ends at the same place is written as a
+    /// single point.
+    ///
+    /// Returns `None` if the span starts and ends in files with different names, since the
+    /// notation has only one path.
+    pub fn from_span(span: &Span) -> Option<Self> {
+        if span.start.filename != span.end.filename {
+            return None;
+        }
+        let start = (span.start.line + 1, span.start.col + 1);
+        let end = (span.end.line + 1, span.end.col + 1);
+        Some(SpanNotation {
+            path: span.start.filename.to_owned(),
+            start,
+            end: if end == start { None } else { Some(end) },
+        })
+    }
+}
+
+impl fmt::Display for SpanNotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.path, self.start.0, self.start.1)?;
+        if let Some((line, col)) = self.end {
+            write!(f, "-{}:{}", line, col)?;
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when a string isn't in `path:line:col` or `path:line:col-line:col` form.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ParseSpanNotationError {
+    input: String,
+}
+
+impl fmt::Display for ParseSpanNotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "expected `path:line:col` or `path:line:col-line:col`, found `{}`",
+            self.input
+        )
+    }
+}
+
+impl error::Error for ParseSpanNotationError {}
+
+impl FromStr for SpanNotation {
+    type Err = ParseSpanNotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseSpanNotationError {
+            input: s.to_owned(),
+        };
+        // A 1-indexed line or column.
+        let number = |part: &str| match part.parse::<usize>() {
+            Ok(0) | Err(_) => Err(err()),
+            Ok(n) => Ok(n),
+        };
+
+        // Split from the right, since paths may contain `:` (e.g. `C:\src\main.rs`).
+        let mut parts = s.rsplitn(3, ':');
+        let last = parts.next().ok_or_else(err)?;
+        let middle = parts.next().ok_or_else(err)?;
+        let rest = parts.next().ok_or_else(err)?;
+        let mut range = middle.splitn(2, '-');
+        let (first, second) = (range.next().ok_or_else(err)?, range.next());
+
+        let (path, start, end) = match second {
+            // `path:line:col-line:col`
+            Some(end_line) => {
+                let mut head = rest.rsplitn(2, ':');
+                let start_line = head.next().ok_or_else(err)?;
+                let path = head.next().ok_or_else(err)?;
+                let start = (number(start_line)?, number(first)?);
+                (path, start, Some((number(end_line)?, number(last)?)))
+            }
+            // `path:line:col`
+            None => (rest, (number(first)?, number(last)?), None),
+        };
+        if path.is_empty() {
+            return Err(err());
+        }
+        Ok(SpanNotation {
+            path: path.to_owned(),
+            start,
+            end,
+        })
+    }
+}
+
+impl SourceFile {
+    /// Find the raw `(start, end)` offsets described by a `SpanNotation`.
+    ///
+    /// The path is matched against file names exactly, taking the first file with that name. A
+    /// single point gives an empty span. Returns `None` if the file, a line, or a column doesn't
+    /// exist, or if the end is before the start.
+    pub fn resolve_notation(&self, notation: &SpanNotation) -> Option<(usize, usize)> {
+        let id = self.file_by_name(&notation.path)?;
+        let offset = |(line, col): (usize, usize)| {
+            self.offset_of_line_col(id, line.checked_sub(1)?, col.checked_sub(1)?)
+        };
+        let start = offset(notation.start)?;
+        let end = match notation.end {
+            Some(end) => offset(end)?,
+            None => start,
+        };
+        if end < start {
+            return None;
+        }
+        Some((start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpanNotation;
+    use SourceFile;
+
+    #[test]
+    fn parse_and_format() {
+        let point: SpanNotation = "src/x.rs:10:5".parse().unwrap();
+        assert_eq!(
+            point,
+            SpanNotation {
+                path: "src/x.rs".into(),
+                start: (10, 5),
+                end: None,
+            }
+        );
+        let span: SpanNotation = r"C:\src\x.rs:1:2-3:4".parse().unwrap();
+        assert_eq!(span.path, r"C:\src\x.rs");
+        assert_eq!((span.start, span.end), ((1, 2), Some((3, 4))));
+        assert_eq!(span.to_string(), r"C:\src\x.rs:1:2-3:4");
+        assert_eq!(point.to_string(), "src/x.rs:10:5");
+
+        for bad in &[
+            "x.rs",
+            "x.rs:1",
+            ":1:1",
+            "x.rs:0:1",
+            "x.rs:1:a",
+            "x.rs:1:2-3",
+        ] {
+            assert!(bad.parse::<SpanNotation>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a.rs", "fn a() {}\n");
+        sourcefile.add_file_raw("b.rs", "fn b() {\n    x\n}\n");
+
+        let notation: SpanNotation = "b.rs:2:5-2:6".parse().unwrap();
+        let (start, end) = sourcefile.resolve_notation(&notation).unwrap();
+        assert_eq!(&sourcefile.contents[start..end], "x");
+        let span = sourcefile.resolve_offset_span(start, end).unwrap();
+        assert_eq!(SpanNotation::from_span(&span), Some(notation));
+        let across = sourcefile.resolve_offset_span(3, 13).unwrap();
+        assert_eq!(SpanNotation::from_span(&across), None);
+
+        let point: SpanNotation = "a.rs:1:4".parse().unwrap();
+        assert_eq!(sourcefile.resolve_notation(&point), Some((3, 3)));
+        for missing in &["c.rs:1:1", "a.rs:2:1", "a.rs:1:11", "b.rs:2:1-1:1"] {
+            let notation: SpanNotation = missing.parse().unwrap();
+            assert_eq!(sourcefile.resolve_notation(&notation), None, "{}", missing);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Translation of offsets between concatenations that share files.
+
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
+use {FileId, SourceFile};
+
+/// Maps global offsets in one `SourceFile` to the same (file, local offset) in another.
+///
+/// Files are matched by name. If a name appears more than once, occurrences are matched in
+/// order. A file is only matched if it has the same length in both, since otherwise local
+/// offsets wouldn't correspond.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct GlobalOffsetMap {
+    /// For each file in the source, its byte range there and its start in the target.
+    files: Vec<(Range<usize>, Option<usize>)>,
+}
+
+impl GlobalOffsetMap {
+    /// Build a map from offsets in `from` to offsets in `to`.
+    pub fn new(from: &SourceFile, to: &SourceFile) -> Self {
+        let mut targets: HashMap<&str, VecDeque<Range<usize>>> = HashMap::new();
+        for (idx, name) in to.file_names.iter().enumerate() {
+            targets
+                .entry(name.as_str())
+                .or_default()
+                .push_back(to.file_span(FileId(idx)));
+        }
+
+        let files = from
+            .file_names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let range = from.file_span(FileId(idx));
+                let target = targets
+                    .get_mut(name.as_str())
+                    .and_then(|queue| queue.pop_front())
+                    .filter(|target| target.len() == range.len())
+                    .map(|target| target.start);
+                (range, target)
+            })
+            .collect();
+        GlobalOffsetMap { files }
+    }
+
+    /// Build a map directly from each source file's range and its start in the target, in
+    /// source order.
+    pub(crate) fn from_files(files: Vec<(Range<usize>, Option<usize>)>) -> Self {
+        GlobalOffsetMap { files }
+    }
+
+    /// Translate an offset, returning `None` if it doesn't fall in a file present in both.
+    pub fn map(&self, offset: usize) -> Option<usize> {
+        let idx = self.files.partition_point(|(range, _)| range.end <= offset);
+        let (range, target) = self.files.get(idx)?;
+        if range.start > offset {
+            return None;
+        }
+        target.map(|start| start + offset - range.start)
+    }
+
+    /// Translate both ends of a span, which must lie in the same file.
+    pub fn map_span(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        let new_start = self.map(start)?;
+        let idx = self.files.partition_point(|(range, _)| range.end <= start);
+        let range = &self.files[idx].0;
+        // `end` may be one past the last byte of the file.
+        if end < start || end > range.end {
+            return None;
+        }
+        Some((new_start, new_start + end - start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GlobalOffsetMap;
+    use SourceFile;
+
+    #[test]
+    fn reordered() {
+        let mut a = SourceFile::new();
+        a.add_file_raw("x", "xx\nx\n");
+        a.add_file_raw("y", "yyy\n");
+        a.add_file_raw("only_a", "a\n");
+        let mut b = SourceFile::new();
+        b.add_file_raw("y", "yyy\n");
+        b.add_file_raw("x", "xx\nx\n");
+
+        let map = GlobalOffsetMap::new(&a, &b);
+        assert_eq!(map.map(0), Some(4));
+        assert_eq!(map.map(4), Some(8));
+        assert_eq!(map.map(6), Some(1));
+        assert_eq!(map.map(10), None);
+        assert_eq!(map.map(100), None);
+        assert_eq!(map.map_span(6, 9), Some((1, 4)));
+        assert_eq!(map.map_span(6, 10), None);
+        assert_eq!(&b.contents[4..6], &a.contents[0..2]);
+    }
+
+    #[test]
+    fn changed_length_is_unmapped() {
+        let mut a = SourceFile::new();
+        a.add_file_raw("x", "old\n");
+        let mut b = SourceFile::new();
+        b.add_file_raw("x", "newer\n");
+        assert_eq!(GlobalOffsetMap::new(&a, &b).map(0), None);
+    }
+}
@@ -0,0 +1,99 @@
+//! Snippets of source from command line flags or configuration rather than files.
+
+use std::fmt;
+
+use {FileId, Position, SourceFile};
+
+impl SourceFile {
+    /// Add source text that came from an option, such as the `expr` of `--define expr`, rather
+    /// than from a file.
+    ///
+    /// The snippet is added like a file named `<option>`, e.g. `<--define>`, so it resolves as
+    /// usual, but it is told apart from files by `snippet_option` rather than by its name, and
+    /// `location` and `format_message` describe positions in it by the option.
+    pub fn add_option_snippet(&mut self, option: impl fmt::Display, text: &str) -> FileId {
+        let option = option.to_string();
+        let id = self.push_file(format!("<{}>", option), text);
+        self.file_snippet_options[id.0] = Some(option);
+        id
+    }
+
+    /// The option a snippet added with `add_option_snippet` came from, or `None` for files.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    #[inline]
+    pub fn snippet_option(&self, id: FileId) -> Option<&str> {
+        self.file_snippet_options[id.0].as_deref()
+    }
+
+    /// Describe where a position is for a message, with 1-indexed numbers.
+    ///
+    /// Positions in files are described as `path:line:col`. Positions in option snippets are
+    /// described by the option, as "`--define` argument, col 7", with the line included only
+    /// if the snippet has more than one.
+    pub fn location(&self, position: &Position) -> String {
+        let snippet = self
+            .file_by_offset(position.offset)
+            .and_then(|id| Some((id, self.snippet_option(id)?)));
+        match snippet {
+            Some((id, option)) if self.line_count(id) > 1 => format!(
+                "`{}` argument, line {}, col {}",
+                option,
+                position.line + 1,
+                position.col + 1
+            ),
+            Some((_, option)) => format!("`{}` argument, col {}", option, position.col + 1),
+            None => format!(
+                "{}:{}:{}",
+                position.filename,
+                position.line + 1,
+                position.col + 1
+            ),
+        }
+    }
+
+    /// The file containing an offset.
+    fn file_by_offset(&self, offset: usize) -> Option<FileId> {
+        let (line_idx, _) = self.locate_line(offset)?;
+        Some(FileId(self.locate_file(line_idx).0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn option_snippet() {
+        let mut sourcefile = SourceFile::new();
+        let file = sourcefile.add_file_raw("main.c", "int x;\n");
+        let define = sourcefile.add_option_snippet("--define", "X=1 +");
+        sourcefile.add_option_snippet("config.prelude", "a\nb\n");
+
+        assert_eq!(sourcefile.file_name(define), "<--define>");
+        assert_eq!(sourcefile.snippet_option(define), Some("--define"));
+        assert_eq!(sourcefile.snippet_option(file), None);
+
+        let in_define = sourcefile.resolve_offset(11).unwrap();
+        assert_eq!(
+            sourcefile.location(&in_define),
+            "`--define` argument, col 5"
+        );
+        let in_config = sourcefile.resolve_offset(14).unwrap();
+        assert_eq!(
+            sourcefile.location(&in_config),
+            "`config.prelude` argument, line 2, col 1"
+        );
+        let in_file = sourcefile.resolve_offset(4).unwrap();
+        assert_eq!(sourcefile.location(&in_file), "main.c:1:5");
+
+        assert_eq!(
+            sourcefile
+                .format_message("{span}: unexpected `{snippet}`", 11, 12)
+                .unwrap(),
+            "`--define` argument, col 5: unexpected `+`"
+        );
+    }
+}
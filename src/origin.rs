@@ -0,0 +1,151 @@
+//! Mapping offsets in text that was transformed when it was added back to the original bytes.
+
+use std::mem::size_of;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use {FileId, SourceFile};
+
+/// How offsets into a file's stored text correspond to offsets into the bytes it was added with.
+///
+/// An empty map is the identity.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct OriginMap {
+    /// `(stored start, original start, linear)` for each run of text, sorted by start. Offsets
+    /// inside a linear run keep their distance from its start; those inside other runs (where the
+    /// text was rewritten) map to the start of the run.
+    segments: Vec<(usize, usize, bool)>,
+}
+
+impl OriginMap {
+    /// Record that a run starts at `stored` in the stored text and `original` in the original.
+    ///
+    /// Runs must be pushed in order.
+    pub(crate) fn push(&mut self, stored: usize, original: usize, linear: bool) {
+        if let Some(&(last_stored, last_original, last_linear)) = self.segments.last() {
+            // A linear run continuing a linear run with the same shift adds nothing.
+            if linear && last_linear && stored - last_stored == original - last_original {
+                return;
+            }
+        } else if linear && stored == original {
+            return;
+        }
+        self.segments.push((stored, original, linear));
+    }
+
+    /// Map an offset into the stored text to one into the original bytes.
+    pub(crate) fn original(&self, stored: usize) -> usize {
+        let idx = self
+            .segments
+            .partition_point(|&(start, _, _)| start <= stored);
+        match idx.checked_sub(1).map(|idx| self.segments[idx]) {
+            Some((start, original, true)) => original + (stored - start),
+            Some((_, original, false)) => original,
+            None => stored,
+        }
+    }
+
+    /// Map an offset into the original bytes to one into the stored text, the reverse of
+    /// `original`. Offsets inside rewritten runs map to the start of the stored run.
+    pub(crate) fn stored(&self, original: usize) -> usize {
+        let idx = self
+            .segments
+            .partition_point(|&(_, start, _)| start <= original);
+        match idx.checked_sub(1).map(|idx| self.segments[idx]) {
+            Some((stored, start, true)) => stored + (original - start),
+            Some((stored, _, false)) => stored,
+            None => original,
+        }
+    }
+
+    /// The map through `self` and then through `earlier`, for text transformed by `earlier`'s
+    /// transformation and then by `self`'s.
+    #[cfg(any(test, feature = "unicode-normalization"))]
+    pub(crate) fn compose(&self, earlier: &OriginMap) -> OriginMap {
+        // Within a run of `self`, offsets only cross a run of `earlier` where that run starts.
+        let mut starts: Vec<usize> = self.segments.iter().map(|&(stored, _, _)| stored).collect();
+        starts.extend(
+            earlier
+                .segments
+                .iter()
+                .map(|&(stored, _, _)| self.stored(stored)),
+        );
+        starts.sort_unstable();
+        starts.dedup();
+
+        let mut composed = OriginMap::default();
+        for stored in starts {
+            let middle = self.original(stored);
+            let linear = self.is_linear(stored) && earlier.is_linear(middle);
+            composed.push(stored, earlier.original(middle), linear);
+        }
+        composed
+    }
+
+    /// Whether the run containing `stored` maps offsets one to one.
+    #[cfg(any(test, feature = "unicode-normalization"))]
+    fn is_linear(&self, stored: usize) -> bool {
+        let idx = self
+            .segments
+            .partition_point(|&(start, _, _)| start <= stored);
+        idx.checked_sub(1).is_none_or(|idx| self.segments[idx].2)
+    }
+
+    pub(crate) fn heap_size(&self) -> usize {
+        self.segments.capacity() * size_of::<(usize, usize, bool)>()
+    }
+}
+
+impl SourceFile {
+    /// Map a global offset to the file it is in and the matching offset in the bytes that file was
+    /// originally added with.
+    ///
+    /// This only differs from the offset within the file's text if the text was transformed as it
+    /// was added. Where a run of text was rewritten (e.g. normalized), offsets inside it map to the
+    /// start of the original run.
+    pub fn original_offset(&self, offset: usize) -> Option<(FileId, usize)> {
+        let (line_idx, _) = self.locate_line(offset)?;
+        let (file_idx, _) = self.locate_file(line_idx);
+        let id = FileId(file_idx);
+        let local = offset - self.file_span(id).start;
+        Some((id, self.file_origins[file_idx].original(local)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OriginMap;
+
+    #[test]
+    fn origin_map() {
+        let mut map = OriginMap::default();
+        map.push(0, 0, true);
+        assert!(map.segments.is_empty());
+        // Two bytes rewritten into one at 4, then text shifted by one.
+        map.push(4, 4, false);
+        map.push(5, 6, true);
+        map.push(8, 9, true);
+        assert_eq!(map.original(3), 3);
+        assert_eq!(map.original(4), 4);
+        assert_eq!(map.original(5), 6);
+        assert_eq!(map.original(10), 11);
+        assert_eq!(map.segments.len(), 2);
+        assert_eq!(map.stored(5), 4);
+        assert_eq!(map.stored(6), 5);
+        assert_eq!(map.stored(11), 10);
+
+        // One byte dropped from the start, then the above.
+        let mut earlier = OriginMap::default();
+        earlier.push(0, 1, true);
+        let composed = map.compose(&earlier);
+        for stored in 0..12 {
+            assert_eq!(
+                composed.original(stored),
+                earlier.original(map.original(stored))
+            );
+        }
+        assert!(!composed.is_linear(4));
+    }
+}
@@ -0,0 +1,130 @@
+//! A rough outline of a file from the indentation of its lines.
+
+use std::ops::Range;
+
+use {FileId, SourceFile};
+
+/// A region of an `Outline`: a line and the more indented lines following it.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct OutlineRegion {
+    /// The lines of the region, local to the file. The first is the less indented header line.
+    pub lines: Range<usize>,
+    /// The byte range of those lines in `contents`, including the last line's newline.
+    pub span: Range<usize>,
+    /// How many regions this one is nested in.
+    pub depth: usize,
+}
+
+/// The nested regions of a file by indentation, see `SourceFile::outline`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Outline {
+    /// In order of their first line, so each region comes before those nested in it.
+    regions: Vec<OutlineRegion>,
+}
+
+impl Outline {
+    /// The regions, in order of their first line. Each region comes before the regions nested in
+    /// it.
+    pub fn regions(&self) -> &[OutlineRegion] {
+        &self.regions
+    }
+
+    /// The innermost region containing a byte offset in `contents`, if any.
+    pub fn enclosing(&self, offset: usize) -> Option<&OutlineRegion> {
+        // The regions containing the offset are nested, so the last of them is the innermost.
+        self.regions
+            .iter()
+            .take_while(|region| region.span.start <= offset)
+            .filter(|region| offset < region.span.end)
+            .last()
+    }
+}
+
+impl SourceFile {
+    /// Outline a file by indentation, for when there's no parser to give its real structure.
+    ///
+    /// Each line followed by more indented lines starts a region, which ends at the last of them
+    /// before a line indented no further than the first. Blank lines don't count, and aren't
+    /// included at the end of a region. Indentation is measured in leading spaces and tabs, with
+    /// a tab counting as one.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn outline(&self, id: FileId) -> Outline {
+        let first_line = self.file_line_indices(id).start;
+        let mut regions = Vec::new();
+        // Lines that may still start a region, as (indent, line), with increasing indents.
+        let mut open: Vec<(usize, usize)> = Vec::new();
+        let mut last_nonblank = 0;
+        for line_idx in self.file_line_indices(id) {
+            let line = &self.contents[self.line_offset(line_idx)..self.line_ends[line_idx]];
+            let text = line.trim_start_matches([' ', '\t']);
+            if text.trim().is_empty() {
+                continue;
+            }
+            let indent = line.len() - text.len();
+            while open.last().is_some_and(|&(top, _)| top >= indent) {
+                let (_, start) = open.pop().unwrap();
+                self.close_region(&mut regions, start, last_nonblank, open.len(), first_line);
+            }
+            open.push((indent, line_idx));
+            last_nonblank = line_idx;
+        }
+        while let Some((_, start)) = open.pop() {
+            self.close_region(&mut regions, start, last_nonblank, open.len(), first_line);
+        }
+        regions.sort_by_key(|region| region.lines.start);
+        Outline { regions }
+    }
+
+    /// Record the region from line `start` to line `last`, if it has more than its header.
+    fn close_region(
+        &self,
+        regions: &mut Vec<OutlineRegion>,
+        start: usize,
+        last: usize,
+        depth: usize,
+        first_line: usize,
+    ) {
+        if last > start {
+            regions.push(OutlineRegion {
+                lines: start - first_line..last + 1 - first_line,
+                span: self.line_offset(start)..self.line_ends[last],
+                depth,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn outline() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("before", "x\n  y\n");
+        let id = sourcefile.add_file_raw(
+            "a.py",
+            "class A:\n    def f():\n        pass\n\n    def g():\n        pass\n\nz = 1\n",
+        );
+        let outline = sourcefile.outline(id);
+        let regions: Vec<_> = outline
+            .regions()
+            .iter()
+            .map(|region| (region.lines.clone(), region.depth))
+            .collect();
+        assert_eq!(regions, vec![(0..6, 0), (1..3, 1), (4..6, 1)]);
+
+        let base = sourcefile.file_span(id).start;
+        assert_eq!(outline.regions()[1].span, base + 9..base + 35);
+        let pass = base + 57;
+        assert_eq!(outline.enclosing(pass).unwrap().lines, 4..6);
+        assert_eq!(outline.enclosing(base).unwrap().lines, 0..6);
+        assert_eq!(outline.enclosing(base + 65), None);
+
+        let flat = sourcefile.add_file_raw("flat", "a\nb\n");
+        assert!(sourcefile.outline(flat).regions().is_empty());
+    }
+}
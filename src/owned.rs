@@ -0,0 +1,84 @@
+//! Positions and spans that own their file names, to outlive the `SourceFile` or leave the
+//! process.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use {Position, Span};
+
+/// A `Position` that owns its file name.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OwnedPosition {
+    /// Name of the file the position is in.
+    pub filename: String,
+    /// 0-indexed line number of position.
+    pub line: usize,
+    /// 0-indexed column number of position.
+    pub col: usize,
+    /// Byte offset of the position in `contents`.
+    pub offset: usize,
+}
+
+impl OwnedPosition {
+    /// Borrow as a `Position`, e.g. to check it with `SourceFile::verify_position`.
+    pub fn as_position<'a>(&'a self) -> Position<'a> {
+        Position::new(&self.filename, self.line, self.col, self.offset)
+    }
+}
+
+impl<'a> From<Position<'a>> for OwnedPosition {
+    fn from(position: Position<'a>) -> Self {
+        OwnedPosition {
+            filename: position.filename.to_owned(),
+            line: position.line,
+            col: position.col,
+            offset: position.offset,
+        }
+    }
+}
+
+/// A `Span` that owns its file names.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OwnedSpan {
+    pub start: OwnedPosition,
+    pub end: OwnedPosition,
+}
+
+impl OwnedSpan {
+    /// Borrow as a `Span`.
+    pub fn as_span<'a>(&'a self) -> Span<'a> {
+        Span {
+            start: self.start.as_position(),
+            end: self.end.as_position(),
+        }
+    }
+}
+
+impl<'a> From<Span<'a>> for OwnedSpan {
+    fn from(span: Span<'a>) -> Self {
+        OwnedSpan {
+            start: span.start.into(),
+            end: span.end.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OwnedSpan;
+    use SourceFile;
+
+    #[test]
+    fn owned() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\n");
+        sourcefile.add_file_raw("b", "two\n");
+        let span = sourcefile.resolve_offset_span(1, 5).unwrap();
+        let owned = OwnedSpan::from(span);
+        assert_eq!(owned.end.filename, "b");
+        assert_eq!(owned.as_span(), span);
+        assert!(sourcefile.verify_position(&owned.start.as_position()));
+    }
+}
@@ -0,0 +1,134 @@
+//! Caching the line index between runs in a compact binary format.
+
+use std::io::{self, Read, Write};
+
+use wire::{read_varint, write_varint};
+use SourceFile;
+
+/// The start of every index, followed by a format version.
+const MAGIC: &[u8; 4] = b"SFIX";
+const VERSION: u8 = 1;
+
+impl SourceFile {
+    /// Write the files' names, line index and text to `writer`, for `read_index` to read back
+    /// without finding the lines again.
+    ///
+    /// The format is the magic bytes `SFIX` and a version byte, the number of files, then for
+    /// each file its name and the length of each of its lines, then `contents`. Numbers use the
+    /// same varint encoding as `SpanWriter`, so lines under 251 bytes take one byte each. Other
+    /// per-file data, such as paths, languages and line annotations, isn't written, and nor are
+    /// settings. Wrap `writer` in a `BufWriter` if it is a file.
+    pub fn write_index(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        write_varint(&mut writer, self.file_names.len() as u64)?;
+        for (idx, name) in self.file_names.iter().enumerate() {
+            write_varint(&mut writer, name.len() as u64)?;
+            writer.write_all(name.as_bytes())?;
+            let lines = self.file_line_start(idx)..self.file_line_ends[idx];
+            write_varint(&mut writer, lines.len() as u64)?;
+            for line_idx in lines {
+                write_varint(&mut writer, self.line_len(line_idx) as u64)?;
+            }
+        }
+        writer.write_all(self.contents.as_bytes())
+    }
+
+    /// Read an index written by `write_index`.
+    ///
+    /// The text is checked to be UTF-8 with each line ending on a char boundary, but not
+    /// searched for newlines, so this is quicker than adding the files again. Use
+    /// `verify_index` to check that the lines are where the index says. Fails with
+    /// `InvalidData` if the data isn't an index, and `UnexpectedEof` if it is cut short.
+    pub fn read_index(mut reader: impl Read) -> io::Result<SourceFile> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(invalid_data("not a sourcefile index"));
+        }
+        let mut sourcefile = SourceFile::new();
+        let files = read_len(&mut reader)?;
+        let mut len: usize = 0;
+        for _ in 0..files {
+            let name_len = read_len(&mut reader)?;
+            let name = read_string(&mut reader, name_len, "file name isn't UTF-8")?;
+            for _ in 0..read_len(&mut reader)? {
+                let line_len = read_len(&mut reader)?;
+                if line_len == 0 {
+                    return Err(invalid_data("empty line in index"));
+                }
+                len = len
+                    .checked_add(line_len)
+                    .ok_or_else(|| invalid_data("index too long"))?;
+                sourcefile.line_ends.push(len);
+            }
+            sourcefile.push_file_entry(name);
+        }
+
+        let contents = read_string(&mut reader, len, "text isn't UTF-8")?;
+        if !sourcefile
+            .line_ends
+            .iter()
+            .all(|&end| contents.is_char_boundary(end))
+        {
+            return Err(invalid_data("line ends inside a char"));
+        }
+        sourcefile.contents = contents;
+        Ok(sourcefile)
+    }
+}
+
+fn read_len<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let len = read_varint(reader)?;
+    if len > usize::MAX as u64 {
+        return Err(invalid_data("length out of range"));
+    }
+    Ok(len as usize)
+}
+
+/// Read `len` bytes of UTF-8, without trusting `len` enough to allocate it all up front.
+fn read_string<R: Read>(reader: &mut R, len: usize, not_utf8: &str) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data(not_utf8))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use SourceFile;
+
+    #[test]
+    fn index_round_trip() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo");
+        sourcefile.add_file_raw("empty", "");
+        sourcefile.add_file_raw("b", "é\r\n");
+        let mut bytes = Vec::new();
+        sourcefile.write_index(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 5 + 1 + 5 + 7 + 4 + sourcefile.contents.len());
+
+        let read = SourceFile::read_index(&bytes[..]).unwrap();
+        assert_eq!(read, sourcefile);
+        assert_eq!(read.resolve_offset(8), sourcefile.resolve_offset(8));
+        read.verify_index().unwrap();
+
+        let cut = SourceFile::read_index(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(cut.kind(), io::ErrorKind::UnexpectedEof);
+        let not_index = SourceFile::read_index(&b"hello"[..]).unwrap_err();
+        assert_eq!(not_index.kind(), io::ErrorKind::InvalidData);
+        // End the second line of "a" inside the "é" of "b".
+        let mut inside_char = bytes.clone();
+        inside_char[10] = 4;
+        inside_char[21] = 3;
+        assert!(SourceFile::read_index(&inside_char[..]).is_err());
+    }
+}
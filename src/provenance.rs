@@ -0,0 +1,80 @@
+//! Virtual files made by copying regions of other files.
+
+use std::fmt;
+
+use {FileId, SourceFile};
+
+impl SourceFile {
+    /// Append a copy of the text `start..end` as a new file called `name`, remembering where it
+    /// was copied from.
+    ///
+    /// This is for template and macro expanders that duplicate input text: diagnostics in the
+    /// copy can be traced back to the original with `trace_to_origin`. Returns `None` if the span
+    /// is reversed, out of bounds or not on character boundaries.
+    pub fn duplicate_region(
+        &mut self,
+        start: usize,
+        end: usize,
+        name: impl fmt::Display,
+    ) -> Option<FileId> {
+        if end < start {
+            return None;
+        }
+        let text = self.contents.get(start..end)?.to_owned();
+        let id = self.push_file(name.to_string(), &text);
+        self.file_provenance[id.0] = Some((start, end));
+        Some(id)
+    }
+
+    /// The span a file was copied from, if it was made by `duplicate_region`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn provenance(&self, id: FileId) -> Option<(usize, usize)> {
+        self.file_provenance[id.0]
+    }
+
+    /// Follow an offset back through any number of `duplicate_region` copies to the text it was
+    /// originally copied from. Offsets that aren't in a copy are returned unchanged.
+    pub fn trace_to_origin(&self, mut offset: usize) -> Option<usize> {
+        loop {
+            let (line_idx, _) = self.locate_line(offset)?;
+            let (file_idx, _) = self.locate_file(line_idx);
+            let id = FileId(file_idx);
+            match self.file_provenance[file_idx] {
+                Some((start, _)) => offset = start + (offset - self.file_span(id).start),
+                None => return Some(offset),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn duplicate() {
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file_raw("a", "{% for x %}\nbody {{x}}\n{% end %}\n");
+        let copy = sourcefile
+            .duplicate_region(12, 22, "a (iteration 1)")
+            .unwrap();
+        let copy2 = sourcefile
+            .duplicate_region(33, 43, "a (iteration 2)")
+            .unwrap();
+
+        assert_eq!(sourcefile.provenance(a), None);
+        assert_eq!(sourcefile.provenance(copy), Some((12, 22)));
+        assert_eq!(&sourcefile.contents[33..43], "body {{x}}");
+        let pos = sourcefile.resolve_offset(38).unwrap();
+        assert_eq!((pos.filename, pos.col), ("a (iteration 1)", 5));
+        assert_eq!(sourcefile.trace_to_origin(38), Some(17));
+        // A copy of a copy traces all the way back.
+        assert_eq!(sourcefile.provenance(copy2), Some((33, 43)));
+        assert_eq!(sourcefile.trace_to_origin(44), Some(13));
+        assert_eq!(sourcefile.trace_to_origin(3), Some(3));
+        assert!(sourcefile.duplicate_region(5, 100, "bad").is_none());
+    }
+}
@@ -0,0 +1,160 @@
+//! Adding files from arbitrary readers, validating UTF-8 as the data arrives.
+
+use std::io::{self, Read};
+use std::{fmt, str};
+
+use {FileId, SourceFile};
+
+/// The size of the chunks read from a reader.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+impl SourceFile {
+    /// Read a file from `reader` and concatenate it to the end of `contents` under the name `name`.
+    ///
+    /// Returns an error of kind `InvalidData` if the data isn't valid UTF-8, in which case nothing
+    /// is added.
+    pub fn add_reader(&mut self, name: impl fmt::Display, reader: impl Read) -> io::Result<FileId> {
+        let (contents, _) = read_utf8(reader, false)?;
+        Ok(self.add_file_raw(name, contents))
+    }
+
+    /// Like `add_reader`, but replaces invalid UTF-8 with U+FFFD instead of failing.
+    ///
+    /// The byte ranges of the replacement characters are recorded, see `replaced_ranges`.
+    pub fn add_reader_lossy(
+        &mut self,
+        name: impl fmt::Display,
+        reader: impl Read,
+    ) -> io::Result<FileId> {
+        let (contents, replaced) = read_utf8(reader, true)?;
+        let id = self.add_file_raw(name, contents);
+        // The text may have lost a byte order mark or line endings as it was added.
+        let origins = &self.file_origins[id.0];
+        let replaced = replaced
+            .into_iter()
+            .map(|(start, end)| (origins.stored(start), origins.stored(end)))
+            .collect();
+        self.file_replacements[id.0] = replaced;
+        Ok(id)
+    }
+
+    /// The `(start, end)` byte ranges, relative to the start of the file, where invalid UTF-8 was
+    /// replaced with U+FFFD by `add_reader_lossy`. Empty for files added any other way.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn replaced_ranges(&self, id: FileId) -> &[(usize, usize)] {
+        &self.file_replacements[id.0]
+    }
+}
+
+/// Read all of `reader` as UTF-8, a chunk at a time.
+///
+/// If `lossy`, each invalid sequence becomes U+FFFD and its range in the output is returned.
+fn read_utf8(mut reader: impl Read, lossy: bool) -> io::Result<(String, Vec<(usize, usize)>)> {
+    let mut text = String::new();
+    let mut replaced = Vec::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    // Bytes at the start of `buf` left over from a sequence split across reads.
+    let mut pending = 0;
+    loop {
+        let read = match reader.read(&mut buf[pending..]) {
+            Ok(read) => read,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let eof = read == 0;
+        let end = pending + read;
+        let mut pos = 0;
+        while pos < end {
+            let err = match str::from_utf8(&buf[pos..end]) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    pos = end;
+                    break;
+                }
+                Err(err) => err,
+            };
+            let valid_end = pos + err.valid_up_to();
+            // Can't fail: we just checked these bytes are valid.
+            text.push_str(str::from_utf8(&buf[pos..valid_end]).unwrap());
+            let invalid_len = match err.error_len() {
+                Some(len) => len,
+                // The sequence may be completed by the next read.
+                None if !eof => {
+                    pos = valid_end;
+                    break;
+                }
+                None => end - valid_end,
+            };
+            if !lossy {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+            replaced.push((text.len(), text.len() + '\u{FFFD}'.len_utf8()));
+            text.push('\u{FFFD}');
+            pos = valid_end + invalid_len;
+        }
+        if eof {
+            return Ok((text, replaced));
+        }
+        buf.copy_within(pos..end, 0);
+        pending = end - pos;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_utf8;
+    use std::io::{self, Read};
+    use SourceFile;
+
+    /// A reader that returns one byte per read, to split every multi-byte sequence.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((&byte, rest)) if !buf.is_empty() => {
+                    buf[0] = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn split_sequences() {
+        let text = "h\u{e9}llo \u{1f600}\n";
+        let (read, replaced) = read_utf8(Trickle(text.as_bytes()), false).unwrap();
+        assert_eq!(read, text);
+        assert!(replaced.is_empty());
+    }
+
+    #[test]
+    fn lossy() {
+        let bytes = b"ok\xff\xfe ok\n\xe2\x82";
+        let mut sourcefile = SourceFile::new();
+        assert_eq!(
+            sourcefile
+                .add_reader("bad", Trickle(bytes))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+        sourcefile.add_reader("good", &b"fine\n"[..]).unwrap();
+
+        let id = sourcefile.add_reader_lossy("bad", Trickle(bytes)).unwrap();
+        assert_eq!(
+            sourcefile.files().last().unwrap().contents,
+            "ok\u{fffd}\u{fffd} ok\n\u{fffd}"
+        );
+        assert_eq!(sourcefile.replaced_ranges(id), &[(2, 5), (5, 8), (12, 15)]);
+        assert_eq!(
+            String::from_utf8_lossy(bytes),
+            "ok\u{fffd}\u{fffd} ok\n\u{fffd}"
+        );
+    }
+}
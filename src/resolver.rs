@@ -0,0 +1,152 @@
+//! A cursor for resolving offsets in roughly sorted order.
+
+use {Position, SourceFile, Span};
+
+/// How many lines a `Resolver` walks from its last line before giving up and searching.
+const MAX_SCAN_LINES: usize = 8;
+
+/// Resolves offsets starting from where the last one was found, see `SourceFile::resolver`.
+#[derive(Debug, Clone)]
+pub struct Resolver<'a> {
+    sourcefile: &'a SourceFile,
+    /// The global index of the last line resolved into.
+    line_idx: usize,
+    /// The index of the file containing that line.
+    file_idx: usize,
+}
+
+impl<'a> Resolver<'a> {
+    /// Get the file, line, and col position of a byte offset, as `SourceFile::resolve_offset`
+    /// does.
+    ///
+    /// Offsets on or near the line of the previous one are found by walking from it, which is
+    /// O(1). Anything further away is found by binary search as usual.
+    pub fn resolve(&mut self, offset: usize) -> Option<Position<'a>> {
+        let sourcefile = self.sourcefile;
+        let line_ends = &sourcefile.line_ends;
+        if offset >= *line_ends.last()? {
+            return None;
+        }
+
+        let mut line_idx = self.line_idx;
+        let mut steps = 0;
+        while steps < MAX_SCAN_LINES && line_ends[line_idx] <= offset {
+            line_idx += 1;
+            steps += 1;
+        }
+        while steps < MAX_SCAN_LINES && sourcefile.line_offset(line_idx) > offset {
+            line_idx -= 1;
+            steps += 1;
+        }
+        let line_start = sourcefile.line_offset(line_idx);
+        let (line_idx, line_start, file_idx) =
+            if line_start <= offset && offset < line_ends[line_idx] {
+                // The file is near too, though there may be empty files to step over.
+                let mut file_idx = self.file_idx;
+                while sourcefile.file_line_ends[file_idx] <= line_idx {
+                    file_idx += 1;
+                }
+                while sourcefile.file_line_start(file_idx) > line_idx {
+                    file_idx -= 1;
+                }
+                (line_idx, line_start, file_idx)
+            } else {
+                let (line_idx, line_start) = sourcefile.locate_line(offset)?;
+                (line_idx, line_start, sourcefile.locate_file(line_idx).0)
+            };
+
+        self.line_idx = line_idx;
+        self.file_idx = file_idx;
+        Some(Position::new(
+            &sourcefile.file_names[file_idx],
+            line_idx - sourcefile.file_line_start(file_idx),
+            offset - line_start,
+            offset,
+        ))
+    }
+}
+
+impl SourceFile {
+    /// A cursor for resolving many offsets that come in nearly sorted order, as from a lexer.
+    pub fn resolver<'a>(&'a self) -> Resolver<'a> {
+        Resolver {
+            sourcefile: self,
+            line_idx: 0,
+            file_idx: 0,
+        }
+    }
+
+    /// Resolve a batch of offsets into `out`, which is cleared first, as `resolve_offset` does.
+    ///
+    /// Reusing `out` from call to call avoids allocating once its capacity has grown to fit. The
+    /// offsets are resolved with a `Resolver`, so nearly sorted batches are cheap.
+    pub fn resolve_offsets_into<'a>(
+        &'a self,
+        offsets: &[usize],
+        out: &mut Vec<Option<Position<'a>>>,
+    ) {
+        out.clear();
+        let mut resolver = self.resolver();
+        out.extend(offsets.iter().map(|&offset| resolver.resolve(offset)));
+    }
+
+    /// Resolve a batch of `(start, end)` spans into `out`, which is cleared first, as
+    /// `resolve_offset_span` does.
+    pub fn resolve_offset_spans_into<'a>(
+        &'a self,
+        spans: &[(usize, usize)],
+        out: &mut Vec<Option<Span<'a>>>,
+    ) {
+        out.clear();
+        out.extend(
+            spans
+                .iter()
+                .map(|&(start, end)| self.resolve_offset_span(start, end)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn resolver() {
+        let mut sourcefile = SourceFile::new();
+        for idx in 0..20 {
+            sourcefile.add_file_raw(idx, "a\nbc\n\nd");
+            sourcefile.add_file_raw("empty", "");
+        }
+        let len = sourcefile.contents.len();
+        let mut resolver = sourcefile.resolver();
+        // Forwards, backwards, and jumping about.
+        let offsets = (0..len)
+            .chain((0..len).rev())
+            .chain((0..len).map(|offset| offset * 37 % len));
+        for offset in offsets {
+            assert_eq!(resolver.resolve(offset), sourcefile.resolve_offset(offset));
+        }
+        assert_eq!(resolver.resolve(len), None);
+        assert_eq!(SourceFile::new().resolver().resolve(0), None);
+    }
+
+    #[test]
+    fn resolve_into() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "ab\ncd\n");
+        let mut positions = vec![None; 10];
+        sourcefile.resolve_offsets_into(&[4, 0, 9], &mut positions);
+        assert_eq!(
+            positions,
+            vec![
+                sourcefile.resolve_offset(4),
+                sourcefile.resolve_offset(0),
+                None
+            ]
+        );
+
+        let mut spans = Vec::new();
+        sourcefile.resolve_offset_spans_into(&[(0, 4), (2, 1)], &mut spans);
+        assert_eq!(spans, vec![sourcefile.resolve_offset_span(0, 4), None]);
+    }
+}
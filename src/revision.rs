@@ -0,0 +1,111 @@
+//! Spans tagged with the revision of the `SourceFile` they were made against.
+
+use std::{error, fmt};
+
+use {SourceFile, Span};
+
+/// A span of `contents`, remembering the revision it was created in. See `SourceFile::tag_span`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct TaggedSpan {
+    /// The start of the span.
+    pub start: usize,
+    /// The end of the span.
+    pub end: usize,
+    revision: u64,
+}
+
+impl TaggedSpan {
+    /// The revision of the `SourceFile` this span was created against.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+}
+
+/// The error returned when resolving a `TaggedSpan` from an older revision.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Stale {
+    /// The revision the span was created against.
+    pub span_revision: u64,
+    /// The revision of the `SourceFile` it was resolved against.
+    pub current_revision: u64,
+}
+
+impl fmt::Display for Stale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "span from revision {} resolved against revision {}",
+            self.span_revision, self.current_revision
+        )
+    }
+}
+
+impl error::Error for Stale {}
+
+impl SourceFile {
+    /// The current revision.
+    ///
+    /// This changes whenever offsets handed out earlier may no longer mean the same thing, for
+    /// example after `clear`. Adding files doesn't change it, since existing offsets stay valid.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Tag a span with the current revision, so that resolving it later can detect that it has
+    /// gone stale.
+    pub fn tag_span(&self, start: usize, end: usize) -> TaggedSpan {
+        TaggedSpan {
+            start,
+            end,
+            revision: self.revision,
+        }
+    }
+
+    /// Resolve a tagged span, like `resolve_offset_span`.
+    ///
+    /// Returns an error if the span was tagged in a different revision.
+    pub fn resolve_tagged_span<'a>(&'a self, span: TaggedSpan) -> Result<Option<Span<'a>>, Stale> {
+        if span.revision != self.revision {
+            return Err(Stale {
+                span_revision: span.revision,
+                current_revision: self.revision,
+            });
+        }
+        Ok(self.resolve_offset_span(span.start, span.end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stale;
+    use SourceFile;
+
+    #[test]
+    fn stale() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "some text\n");
+        let span = sourcefile.tag_span(0, 4);
+        sourcefile.add_file_raw("b", "more text\n");
+        assert!(sourcefile.resolve_tagged_span(span).unwrap().is_some());
+
+        sourcefile.clear();
+        sourcefile.add_file_raw("c", "new text\n");
+        assert_eq!(
+            sourcefile.resolve_tagged_span(span),
+            Err(Stale {
+                span_revision: 0,
+                current_revision: 1,
+            })
+        );
+        let fresh = sourcefile.tag_span(0, 3);
+        assert_eq!(
+            sourcefile
+                .resolve_tagged_span(fresh)
+                .unwrap()
+                .unwrap()
+                .start
+                .filename,
+            "c"
+        );
+    }
+}
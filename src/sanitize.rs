@@ -0,0 +1,116 @@
+//! Making source lines safe to print to a terminal.
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use {FileId, SourceFile};
+
+/// A line of source with control chars replaced by visible stand-ins, see
+/// `SanitizedLine::new`.
+///
+/// Replacements are longer than the chars they replace, so use `col` to find where a column of
+/// the original line ended up, e.g. to place a caret.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SanitizedLine {
+    /// The text to print.
+    pub text: String,
+    /// For each replaced char, its byte range in the original line and that of its replacement
+    /// in `text`.
+    replaced: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl SanitizedLine {
+    /// Replace the control chars in `line`, other than tab and newline, which renderers handle
+    /// themselves.
+    ///
+    /// C0 controls (such as NUL and ESC) and DEL become their Unicode control pictures (`␀`,
+    /// `␛`, `␡`), and other control chars become escapes like `\u{85}`.
+    pub fn new(line: &str) -> SanitizedLine {
+        let mut text = String::with_capacity(line.len());
+        let mut replaced = Vec::new();
+        for (byte, c) in line.char_indices() {
+            if !c.is_control() || c == '\t' || c == '\n' {
+                text.push(c);
+                continue;
+            }
+            let start = text.len();
+            match c as u32 {
+                code @ 0..=0x1f => text.push(char::from_u32(0x2400 + code).unwrap()),
+                0x7f => text.push('\u{2421}'),
+                // Writing to a `String` can't fail.
+                code => {
+                    let _ = write!(text, "\\u{{{:x}}}", code);
+                }
+            }
+            replaced.push((byte..byte + c.len_utf8(), start..text.len()));
+        }
+        SanitizedLine { text, replaced }
+    }
+
+    /// Whether anything was replaced.
+    pub fn is_changed(&self) -> bool {
+        !self.replaced.is_empty()
+    }
+
+    /// The byte column in `text` of a byte column in the original line.
+    ///
+    /// A column inside a replaced char maps to the start of its replacement.
+    pub fn col(&self, col: usize) -> usize {
+        let before = self.replaced.partition_point(|(orig, _)| orig.end <= col);
+        if let Some((orig, new)) = self.replaced.get(before) {
+            if orig.start < col {
+                return new.start;
+            }
+        }
+        match before {
+            0 => col,
+            n => {
+                let (ref orig, ref new) = self.replaced[n - 1];
+                col - orig.end + new.end
+            }
+        }
+    }
+}
+
+impl SourceFile {
+    /// A 0-indexed line of a file, without its line ending, made safe to print, or `None` if
+    /// the file has no such line.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn sanitized_line(&self, id: FileId, line: usize) -> Option<SanitizedLine> {
+        Some(SanitizedLine::new(self.line(id, line)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SanitizedLine;
+    use SourceFile;
+
+    #[test]
+    fn sanitize() {
+        let line = SanitizedLine::new("a\0b\x1b[31m\tc\u{85}d\x7f");
+        assert_eq!(line.text, "a␀b␛[31m\tc\\u{85}d␡");
+        assert!(line.is_changed());
+        // `b` moves from byte 2 to byte 4, since `␀` takes 3 bytes.
+        assert_eq!(line.col(2), 4);
+        assert_eq!(line.col(0), 0);
+        // `d` is after `␀`, `␛` and the escape of the 2-byte `\u{85}`.
+        assert_eq!(line.col(12), 12 + 2 + 2 + 4);
+        assert_eq!(&line.text[line.col(12)..line.col(13)], "d");
+        // Inside `\u{85}`.
+        assert_eq!(line.col(11), line.col(10));
+        assert_eq!(line.col(14), line.text.len());
+
+        let clean = SanitizedLine::new("fine\tline");
+        assert!(!clean.is_changed());
+        assert_eq!(clean.col(5), 5);
+
+        let mut sourcefile = SourceFile::new();
+        let id = sourcefile.add_file_raw("bin", "\x01\n");
+        assert_eq!(sourcefile.sanitized_line(id, 0).unwrap().text, "␁");
+        assert!(sourcefile.sanitized_line(id, 1).is_none());
+    }
+}
@@ -0,0 +1,59 @@
+//! A `SourceFile` that can no longer be changed.
+
+use std::ops::Deref;
+
+use SourceFile;
+
+/// A `SourceFile` that can't be modified, so offsets into it stay valid for as long as it lives.
+///
+/// Created with `SourceFile::seal`. It dereferences to `&SourceFile`, so every query is
+/// available, but there is no way to get a `&mut SourceFile` back short of `into_inner`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Sealed(SourceFile);
+
+impl Sealed {
+    /// Give up the guarantee and get the `SourceFile` back.
+    pub fn into_inner(self) -> SourceFile {
+        self.0
+    }
+}
+
+impl Deref for Sealed {
+    type Target = SourceFile;
+
+    fn deref(&self) -> &SourceFile {
+        &self.0
+    }
+}
+
+impl AsRef<SourceFile> for Sealed {
+    fn as_ref(&self) -> &SourceFile {
+        &self.0
+    }
+}
+
+impl SourceFile {
+    /// Freeze the `SourceFile`, so that APIs can require a `Sealed` to be sure the offsets they
+    /// store won't be invalidated by later changes. This doesn't copy anything.
+    pub fn seal(self) -> Sealed {
+        Sealed(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn sealed() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "text\n");
+        let sealed = sourcefile.seal();
+        assert_eq!(sealed.resolve_offset(1).unwrap().col, 1);
+        assert_eq!(sealed.contents, "text\n");
+
+        let mut sourcefile = sealed.into_inner();
+        sourcefile.add_file_raw("b", "more\n");
+        assert_eq!(sourcefile.files().count(), 2);
+    }
+}
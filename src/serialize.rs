@@ -0,0 +1,160 @@
+//! Checking a deserialized `SourceFile` before it is used.
+
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use origin::OriginMap;
+use SourceFile;
+
+/// The fields of a `SourceFile` as they were serialized, before they are checked.
+///
+/// Keep in step with the fields of `SourceFile`.
+#[derive(Deserialize)]
+pub(crate) struct Unchecked {
+    contents: String,
+    file_names: Vec<String>,
+    file_paths: Vec<Option<(PathBuf, String)>>,
+    display_base: Option<PathBuf>,
+    tab_width: Option<usize>,
+    strip_bom: bool,
+    normalize_line_endings: bool,
+    file_replacements: Vec<Vec<(usize, usize)>>,
+    file_origins: Vec<OriginMap>,
+    file_provenance: Vec<Option<(usize, usize)>>,
+    file_annotations: Vec<Vec<String>>,
+    file_languages: Vec<Option<String>>,
+    file_snippet_options: Vec<Option<String>>,
+    file_line_ends: Vec<usize>,
+    line_ends: Vec<usize>,
+    line_chars: Option<Vec<usize>>,
+    revision: u64,
+}
+
+impl TryFrom<Unchecked> for SourceFile {
+    type Error = String;
+
+    /// Check that the index describes `contents`, so that lookups on the result can't panic, and
+    /// rebuild the file ends, which aren't serialized.
+    fn try_from(data: Unchecked) -> Result<Self, String> {
+        let files = data.file_names.len();
+        let per_file = [
+            ("file_paths", data.file_paths.len()),
+            ("file_replacements", data.file_replacements.len()),
+            ("file_origins", data.file_origins.len()),
+            ("file_provenance", data.file_provenance.len()),
+            ("file_annotations", data.file_annotations.len()),
+            ("file_languages", data.file_languages.len()),
+            ("file_snippet_options", data.file_snippet_options.len()),
+            ("file_line_ends", data.file_line_ends.len()),
+        ];
+        for &(field, len) in &per_file {
+            if len != files {
+                return Err(format!("{} has {} entries for {} files", field, len, files));
+            }
+        }
+        if !is_sorted(&data.file_line_ends)
+            || data.file_line_ends.last().map_or(0, |&end| end) != data.line_ends.len()
+        {
+            return Err("file_line_ends doesn't match line_ends".to_owned());
+        }
+        // Every line has at least its first char, as `add_file` never records an empty line.
+        if !is_strictly_sorted(&data.line_ends)
+            || data.line_ends.first() == Some(&0)
+            || data.line_ends.last().map_or(0, |&end| end) != data.contents.len()
+            || !data
+                .line_ends
+                .iter()
+                .all(|&end| data.contents.is_char_boundary(end))
+        {
+            return Err("line_ends doesn't match contents".to_owned());
+        }
+        if let Some(ref line_chars) = data.line_chars {
+            let mut line_start = 0;
+            let counts_match = data.line_ends.iter().zip(line_chars).all(|(&end, &chars)| {
+                let line = &data.contents[line_start..end];
+                line_start = end;
+                line.chars().count() == chars
+            });
+            if line_chars.len() != data.line_ends.len() || !counts_match {
+                return Err("line_chars doesn't match line_ends".to_owned());
+            }
+        }
+
+        let file_ends = data
+            .file_line_ends
+            .iter()
+            .map(|&line_end| line_end.checked_sub(1).map_or(0, |idx| data.line_ends[idx]))
+            .collect();
+        let sourcefile = SourceFile {
+            contents: data.contents,
+            file_names: data.file_names,
+            file_paths: data.file_paths,
+            display_base: data.display_base,
+            tab_width: data.tab_width,
+            strip_bom: data.strip_bom,
+            normalize_line_endings: data.normalize_line_endings,
+            file_replacements: data.file_replacements,
+            file_origins: data.file_origins,
+            file_provenance: data.file_provenance,
+            file_annotations: data.file_annotations,
+            file_languages: data.file_languages,
+            file_snippet_options: data.file_snippet_options,
+            file_line_ends: data.file_line_ends,
+            file_ends,
+            line_ends: data.line_ends,
+            line_chars: data.line_chars,
+            revision: data.revision,
+        };
+        sourcefile.verify_index().map_err(|err| err.to_string())?;
+        Ok(sourcefile)
+    }
+}
+
+fn is_sorted(items: &[usize]) -> bool {
+    items.windows(2).all(|pair| pair[0] <= pair[1])
+}
+
+fn is_strictly_sorted(items: &[usize]) -> bool {
+    items.windows(2).all(|pair| pair[0] < pair[1])
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use serde_json;
+    use {OwnedPosition, SourceFile};
+
+    #[test]
+    fn serde_round_trip() {
+        let mut sourcefile = SourceFile::with_char_counts();
+        sourcefile.add_file_raw("a", "one\ntwo\n");
+        sourcefile.add_file_raw("empty", "");
+        sourcefile.add_option_snippet("--define", "x");
+        let json = serde_json::to_string(&sourcefile).unwrap();
+        let back: SourceFile = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, sourcefile);
+        assert_eq!(back.resolve_offset(9), sourcefile.resolve_offset(9));
+
+        let broken = json.replace("\"line_ends\":[4,8,9]", "\"line_ends\":[3,8,9]");
+        assert_ne!(broken, json);
+        assert!(serde_json::from_str::<SourceFile>(&broken).is_err());
+        // An empty last line of `a`, which `add_file` never records.
+        let empty_line = json
+            .replace("\"file_line_ends\":[2,2,3]", "\"file_line_ends\":[3,3,4]")
+            .replace("\"line_ends\":[4,8,9]", "\"line_ends\":[4,8,8,9]")
+            .replace("\"line_chars\":[4,4,1]", "\"line_chars\":[4,4,0,1]");
+        assert!(serde_json::from_str::<SourceFile>(&empty_line).is_err());
+        let wrong_chars = json.replace("\"line_chars\":[4,4,1]", "\"line_chars\":[4,3,1]");
+        assert_ne!(wrong_chars, json);
+        assert!(serde_json::from_str::<SourceFile>(&wrong_chars).is_err());
+
+        let position: OwnedPosition = sourcefile.resolve_offset(5).unwrap().into();
+        let json = serde_json::to_string(&position).unwrap();
+        assert_eq!(json, r#"{"filename":"a","line":1,"col":1,"offset":5}"#);
+        assert_eq!(
+            serde_json::from_str::<OwnedPosition>(&json).unwrap(),
+            position
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Per-span values, such as the types or notes a compiler keeps for its AST nodes.
+
+use std::cmp::Reverse;
+
+#[cfg(feature = "serde")]
+use serde::de::{Deserialize, Deserializer, Error as _};
+#[cfg(feature = "serde")]
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+/// A handle to a span in a `SpanSideTable`, returned when its value is first inserted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SpanId(usize);
+
+impl SpanId {
+    /// The index of the span in the table, counting from 0 in the order spans were first
+    /// inserted.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/// A map from raw `(start, end)` spans to values, with each span interned as a `SpanId`.
+///
+/// Values are stored densely by id, so looking one up by id is an index. Looking one up by span
+/// is a binary search. Iteration is in source order: by start, then longest first, so enclosing
+/// spans come before the spans inside them, as in `SpanTree`. Inserting spans in that order,
+/// as a parser walking the source does, is cheapest.
+///
+/// With the `serde` feature, a table serializes as a sequence of `(start, end, value)` in id
+/// order, so ids are the same after a round trip.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SpanSideTable<T> {
+    /// The span of each id.
+    spans: Vec<(usize, usize)>,
+    /// The value of each id.
+    values: Vec<T>,
+    /// The ids in source order.
+    order: Vec<usize>,
+}
+
+impl<T> Default for SpanSideTable<T> {
+    fn default() -> Self {
+        SpanSideTable {
+            spans: Vec::new(),
+            values: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+}
+
+impl<T> SpanSideTable<T> {
+    /// Create an empty table. Equivalent to `Default::default`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The number of spans in the table.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Whether the table is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Set the value of the span `start..end`, returning its id and its old value, if it had
+    /// one.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the span ends before it starts.
+    pub fn insert(&mut self, start: usize, end: usize, value: T) -> (SpanId, Option<T>) {
+        assert!(
+            start <= end,
+            "span {}..{} ends before it starts",
+            start,
+            end
+        );
+        let key = (start, Reverse(end));
+        let pos = match self.order.last() {
+            // Past the last span in source order, the usual case.
+            Some(&last) if self.key(last) < key => self.order.len(),
+            None => 0,
+            Some(_) => match self.order.binary_search_by_key(&key, |&id| self.key(id)) {
+                Ok(pos) => {
+                    let id = self.order[pos];
+                    let old = std::mem::replace(&mut self.values[id], value);
+                    return (SpanId(id), Some(old));
+                }
+                Err(pos) => pos,
+            },
+        };
+        let id = self.spans.len();
+        self.spans.push((start, end));
+        self.values.push(value);
+        self.order.insert(pos, id);
+        (SpanId(id), None)
+    }
+
+    /// The id of the span `start..end`, if it is in the table.
+    pub fn id(&self, start: usize, end: usize) -> Option<SpanId> {
+        let pos = self
+            .order
+            .binary_search_by_key(&(start, Reverse(end)), |&id| self.key(id))
+            .ok()?;
+        Some(SpanId(self.order[pos]))
+    }
+
+    /// The `(start, end)` span of an id.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this table.
+    #[inline]
+    pub fn span(&self, id: SpanId) -> (usize, usize) {
+        self.spans[id.0]
+    }
+
+    /// The value of an id.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this table.
+    #[inline]
+    pub fn get(&self, id: SpanId) -> &T {
+        &self.values[id.0]
+    }
+
+    /// The value of an id, mutably.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this table.
+    #[inline]
+    pub fn get_mut(&mut self, id: SpanId) -> &mut T {
+        &mut self.values[id.0]
+    }
+
+    /// The value of the span `start..end`, if it is in the table.
+    pub fn get_span(&self, start: usize, end: usize) -> Option<&T> {
+        self.id(start, end).map(|id| self.get(id))
+    }
+
+    /// Iterate over the spans and their values in source order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (SpanId, (usize, usize), &'a T)> + 'a {
+        self.order
+            .iter()
+            .map(move |&id| (SpanId(id), self.spans[id], &self.values[id]))
+    }
+
+    /// Iterate, in source order, over the spans starting in `start..end`, e.g. those in one
+    /// file.
+    pub fn starting_in<'a>(
+        &'a self,
+        start: usize,
+        end: usize,
+    ) -> impl Iterator<Item = (SpanId, (usize, usize), &'a T)> + 'a {
+        let from = self.order.partition_point(|&id| self.spans[id].0 < start);
+        let to = self.order.partition_point(|&id| self.spans[id].0 < end);
+        self.order[from..to.max(from)]
+            .iter()
+            .map(move |&id| (SpanId(id), self.spans[id], &self.values[id]))
+    }
+
+    /// The sort key of an id, giving source order.
+    fn key(&self, id: usize) -> (usize, Reverse<usize>) {
+        let (start, end) = self.spans[id];
+        (start, Reverse(end))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for SpanSideTable<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for (&(start, end), value) in self.spans.iter().zip(&self.values) {
+            seq.serialize_element(&(start, end, value))?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for SpanSideTable<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries: Vec<(usize, usize, T)> = Vec::deserialize(deserializer)?;
+        let mut table = SpanSideTable::new();
+        for (start, end, value) in entries {
+            if end < start {
+                return Err(D::Error::custom(format_args!(
+                    "span {}..{} ends before it starts",
+                    start, end
+                )));
+            }
+            if table.insert(start, end, value).1.is_some() {
+                return Err(D::Error::custom(format_args!(
+                    "span {}..{} is in the table twice",
+                    start, end
+                )));
+            }
+        }
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpanSideTable;
+
+    #[test]
+    fn side_table() {
+        let mut table = SpanSideTable::new();
+        let (outer, _) = table.insert(0, 10, "fn");
+        let (inner, _) = table.insert(3, 5, "arg");
+        let (first, _) = table.insert(0, 2, "name");
+        assert_eq!(table.insert(3, 5, "param"), (inner, Some("arg")));
+        assert_eq!(table.len(), 3);
+        assert_eq!(first.index(), 2);
+
+        let order: Vec<_> = table.iter().map(|(id, span, _)| (id, span)).collect();
+        assert_eq!(
+            order,
+            vec![(outer, (0, 10)), (first, (0, 2)), (inner, (3, 5))]
+        );
+        assert_eq!(table.get_span(3, 5), Some(&"param"));
+        assert_eq!(table.id(3, 6), None);
+        assert_eq!(table.span(inner), (3, 5));
+        *table.get_mut(outer) = "item";
+        assert_eq!(*table.get(outer), "item");
+
+        let starting: Vec<_> = table.starting_in(1, 4).map(|(id, _, _)| id).collect();
+        assert_eq!(starting, vec![inner]);
+        assert_eq!(table.starting_in(5, 4).count(), 0);
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    #[test]
+    fn side_table_serde() {
+        use serde_json;
+
+        let mut table = SpanSideTable::new();
+        table.insert(4, 6, 1);
+        table.insert(0, 9, 2);
+        let json = serde_json::to_string(&table).unwrap();
+        assert_eq!(json, "[[4,6,1],[0,9,2]]");
+        let back: SpanSideTable<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, table);
+        assert!(serde_json::from_str::<SpanSideTable<i32>>("[[1,1,0],[1,1,0]]").is_err());
+    }
+}
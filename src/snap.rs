@@ -0,0 +1,104 @@
+//! Adjusting spans from external tools onto sensible boundaries.
+
+use SourceFile;
+
+/// How `SourceFile::snap_span` widens a span.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum SnapMode {
+    /// Widen each end to the nearest character boundary.
+    CharBoundary,
+    /// Widen each end to the edge of the word (alphanumerics and `_`) it falls inside. Words never
+    /// extend across a line ending.
+    WordBoundary,
+    /// Widen the span to cover whole lines, excluding the line ending of the last line.
+    LineBoundary,
+}
+
+impl SourceFile {
+    /// Widen the span `start..end` so that both ends lie on boundaries of the given kind.
+    ///
+    /// This is for offsets from other tools (regex matches over bytes, other encodings) that may
+    /// land in the middle of a character. Offsets past the end of `contents` are clamped to it.
+    /// Returns `None` if `end < start`.
+    pub fn snap_span(&self, start: usize, end: usize, mode: SnapMode) -> Option<(usize, usize)> {
+        if end < start {
+            return None;
+        }
+        let len = self.contents.len();
+        let (mut start, mut end) = (start.min(len), end.min(len));
+        while !self.contents.is_char_boundary(start) {
+            start -= 1;
+        }
+        while !self.contents.is_char_boundary(end) {
+            end += 1;
+        }
+
+        match mode {
+            SnapMode::CharBoundary => (),
+            SnapMode::WordBoundary => {
+                let is_word = |c: char| c.is_alphanumeric() || c == '_';
+                start = self.contents[..start]
+                    .char_indices()
+                    .rev()
+                    .take_while(|&(_, c)| is_word(c))
+                    .last()
+                    .map_or(start, |(idx, _)| idx);
+                end += self.contents[end..]
+                    .chars()
+                    .take_while(|&c| is_word(c))
+                    .map(char::len_utf8)
+                    .sum::<usize>();
+            }
+            SnapMode::LineBoundary => {
+                if let Some((_, line_start)) = self.locate_line(start) {
+                    start = line_start;
+                }
+                // An end at a line start doesn't include that line.
+                let last = if end > start { end - 1 } else { end };
+                if let Some((line_idx, line_start)) = self.locate_line(last) {
+                    let line = &self.contents[line_start..self.line_ends[line_idx]];
+                    end = line_start + line.trim_end_matches(['\n', '\r']).len();
+                }
+            }
+        }
+        Some((start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapMode;
+    use SourceFile;
+
+    #[test]
+    fn snap() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "let caf\u{e9} = 1;\r\nfoo_bar(x)\n");
+        // 'é' is at bytes 7..9, and the second line starts at 16.
+        assert_eq!(
+            sourcefile.snap_span(8, 8, SnapMode::CharBoundary),
+            Some((7, 9))
+        );
+        assert_eq!(
+            sourcefile.snap_span(5, 6, SnapMode::WordBoundary),
+            Some((4, 9))
+        );
+        assert_eq!(
+            sourcefile.snap_span(20, 21, SnapMode::WordBoundary),
+            Some((16, 23))
+        );
+        assert_eq!(
+            sourcefile.snap_span(5, 19, SnapMode::LineBoundary),
+            Some((0, 26))
+        );
+        assert_eq!(
+            sourcefile.snap_span(2, 16, SnapMode::LineBoundary),
+            Some((0, 14))
+        );
+        assert_eq!(
+            sourcefile.snap_span(0, 100, SnapMode::CharBoundary),
+            Some((0, 27))
+        );
+        assert_eq!(sourcefile.snap_span(3, 2, SnapMode::CharBoundary), None);
+    }
+}
@@ -0,0 +1,437 @@
+//! Source Map v3 documents describing the concatenation, for browsers and devtools, and
+//! resolving positions through source maps made by other tools.
+
+use std::{error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use {ColumnEncoding, Position, SourceFile};
+
+/// A Source Map v3 document, as in the [spec](https://tc39.es/ecma426/).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SourceMap<'a> {
+    version: u32,
+    file: &'a str,
+    sources: &'a [String],
+    sources_content: Vec<&'a str>,
+    names: Vec<String>,
+    mappings: String,
+}
+
+/// The fields of a Source Map v3 document that `SourceMapFile` uses.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportedSourceMap {
+    version: u32,
+    #[serde(default)]
+    source_root: Option<String>,
+    sources: Vec<Option<String>>,
+    #[serde(default)]
+    sources_content: Vec<Option<String>>,
+    mappings: String,
+}
+
+/// Where a run of generated text came from: the index of the source, and the 0-indexed line
+/// and column in it.
+type Origin = (usize, usize, usize);
+
+/// A generated file and the Source Map v3 describing where its text came from, see
+/// `SourceMapFile::from_json`.
+///
+/// This resolves offsets in the generated file to positions in the original sources, as
+/// `SourceFile::resolve_offset` does for a concatenation.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SourceMapFile {
+    /// The text of the generated file.
+    pub generated: String,
+    /// The offset of the start of each generated line.
+    line_starts: Vec<usize>,
+    /// The names of the sources, with the source root prepended.
+    sources: Vec<String>,
+    /// The text of each source, if the source map included it.
+    sources_content: Vec<Option<String>>,
+    /// For each generated line, the UTF-16 column where each mapping starts, and where its text
+    /// came from if it is mapped, sorted by column.
+    segments: Vec<Vec<(usize, Option<Origin>)>>,
+}
+
+/// Why a Source Map v3 document couldn't be read, see `SourceMapFile::from_json`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SourceMapError {
+    /// The text isn't a JSON source map. Index maps, with `sections`, aren't supported.
+    Json(serde_json::Error),
+    /// The source map isn't version 3.
+    Version(u32),
+    /// The `mappings` are malformed on this 0-indexed generated line.
+    Mappings {
+        /// The generated line.
+        line: usize,
+    },
+}
+
+impl fmt::Display for SourceMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SourceMapError::Json(ref err) => write!(f, "invalid source map: {}", err),
+            SourceMapError::Version(version) => {
+                write!(f, "unsupported source map version {}", version)
+            }
+            SourceMapError::Mappings { line } => {
+                write!(f, "invalid mappings on generated line {}", line + 1)
+            }
+        }
+    }
+}
+
+impl error::Error for SourceMapError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            SourceMapError::Json(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl SourceMapFile {
+    /// Read a Source Map v3 document describing `generated`.
+    ///
+    /// Generated lines are split at `\n` only, as for `SourceFile::source_map`. The `names`
+    /// field is ignored.
+    pub fn from_json(
+        source_map: &str,
+        generated: impl Into<String>,
+    ) -> Result<Self, SourceMapError> {
+        let map: ImportedSourceMap =
+            serde_json::from_str(source_map).map_err(SourceMapError::Json)?;
+        if map.version != 3 {
+            return Err(SourceMapError::Version(map.version));
+        }
+        let root = match map.source_root {
+            Some(ref root) if !root.is_empty() && !root.ends_with('/') => format!("{}/", root),
+            Some(root) => root,
+            None => String::new(),
+        };
+        let sources: Vec<String> = map
+            .sources
+            .into_iter()
+            .map(|source| format!("{}{}", root, source.unwrap_or_default()))
+            .collect();
+        let mut sources_content = map.sources_content;
+        sources_content.resize(sources.len(), None);
+
+        let generated = generated.into();
+        let mut line_starts = vec![0];
+        line_starts.extend(generated.match_indices('\n').map(|(idx, _)| idx + 1));
+
+        // Every field but the generated column carries on from the last segment of the line
+        // before.
+        let mut origin = [0i64; 3];
+        let mut segments = Vec::new();
+        for (line, line_mappings) in map.mappings.split(';').enumerate() {
+            let invalid = || SourceMapError::Mappings { line };
+            let mut col = 0i64;
+            let mut line_segments = Vec::new();
+            for segment in line_mappings
+                .split(',')
+                .filter(|segment| !segment.is_empty())
+            {
+                let fields = read_vlqs(segment).ok_or_else(invalid)?;
+                col = col.checked_add(fields[0]).ok_or_else(invalid)?;
+                if col < 0 {
+                    return Err(invalid());
+                }
+                let mapped = match fields.len() {
+                    1 => None,
+                    4 | 5 => {
+                        for (field, delta) in origin.iter_mut().zip(&fields[1..4]) {
+                            *field = field.checked_add(*delta).ok_or_else(invalid)?;
+                        }
+                        if origin.iter().any(|&field| field < 0)
+                            || origin[0] as usize >= sources.len()
+                        {
+                            return Err(invalid());
+                        }
+                        Some((origin[0] as usize, origin[1] as usize, origin[2] as usize))
+                    }
+                    _ => return Err(invalid()),
+                };
+                line_segments.push((col as usize, mapped));
+            }
+            line_segments.sort_by_key(|&(col, _)| col);
+            segments.push(line_segments);
+        }
+        Ok(SourceMapFile {
+            generated,
+            line_starts,
+            sources,
+            sources_content,
+            segments,
+        })
+    }
+
+    /// The names of the original sources, with any source root prepended.
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// Get the original file, line, and col position of a byte offset into `generated`.
+    ///
+    /// As in browsers, the offset takes the position of the nearest mapping at or before it on
+    /// its line. Returns `None` if there is no such mapping, or it is unmapped, or the offset
+    /// isn't on a char boundary before the end of `generated`.
+    ///
+    /// The column is converted to bytes if the source map includes the source's text, and is
+    /// left in the UTF-16 code units source maps use if it doesn't. The position's offset is the
+    /// offset that was resolved.
+    pub fn resolve_offset<'a>(&'a self, offset: usize) -> Option<Position<'a>> {
+        if offset >= self.generated.len() {
+            return None;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let before = self.generated.get(self.line_starts[line]..offset)?;
+        let col = ColumnEncoding::Utf16.column(before, before.len());
+        let segments = self.segments.get(line)?;
+        let idx = segments.partition_point(|&(start, _)| start <= col);
+        let (source, orig_line, orig_col) = segments[idx.checked_sub(1)?].1?;
+        let orig_col = self.sources_content[source]
+            .as_ref()
+            .and_then(|content| content.split('\n').nth(orig_line))
+            .and_then(|text| ColumnEncoding::Utf16.byte(text, orig_col))
+            .unwrap_or(orig_col);
+        Some(Position::new(
+            &self.sources[source],
+            orig_line,
+            orig_col,
+            offset,
+        ))
+    }
+}
+
+/// Read the base64 VLQs of one segment.
+fn read_vlqs(segment: &str) -> Option<Vec<i64>> {
+    let mut values = Vec::new();
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    for byte in segment.bytes() {
+        let digit = BASE64.iter().position(|&b| b == byte)? as u64;
+        if shift > 60 {
+            return None;
+        }
+        value |= (digit & 31) << shift;
+        if digit & 32 != 0 {
+            shift += 5;
+            continue;
+        }
+        let magnitude = (value >> 1) as i64;
+        values.push(if value & 1 == 1 {
+            -magnitude
+        } else {
+            magnitude
+        });
+        value = 0;
+        shift = 0;
+    }
+    if shift != 0 {
+        return None;
+    }
+    Some(values)
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Append `value` to `out` as a base64 VLQ.
+fn write_vlq(out: &mut String, value: i64) {
+    let mut vlq = if value < 0 {
+        ((-value as u64) << 1) | 1
+    } else {
+        (value as u64) << 1
+    };
+    loop {
+        let mut digit = (vlq & 31) as usize;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 32;
+        }
+        out.push(BASE64[digit] as char);
+        if vlq == 0 {
+            return;
+        }
+    }
+}
+
+impl SourceFile {
+    /// A Source Map v3 document mapping `contents`, served as `file`, back to the files it was
+    /// concatenated from, as JSON.
+    ///
+    /// The files are the `sources`, by name, with their text in `sourcesContent`. There is a
+    /// mapping at the start of each line and of each run of non-whitespace chars, so errors
+    /// reported at a token map to the token. Columns are counted in UTF-16 code units, as the
+    /// spec requires. Generated lines are split at `\n` only, as the index is, so text with lone
+    /// `\r` or U+2028 line terminators maps to the wrong lines.
+    pub fn source_map(&self, file: &str) -> String {
+        let mut mappings = String::new();
+        // The last segment's fields, which the next one is written relative to. The generated
+        // column starts again from 0 on each generated line.
+        let mut last = [0i64; 4];
+        // The column on its generated line where the current line starts, which isn't 0 after a
+        // file that doesn't end in a newline.
+        let mut line_base = 0;
+        let mut first_on_line = true;
+        for file_idx in 0..self.file_names.len() {
+            let lines = self.file_line_start(file_idx)..self.file_line_ends[file_idx];
+            for (line, line_idx) in lines.enumerate() {
+                let text = &self.contents[self.line_offset(line_idx)..self.line_ends[line_idx]];
+                let mut col = 0;
+                let mut after_space = true;
+                for c in text.chars() {
+                    if col == 0 || (after_space && !c.is_whitespace()) {
+                        let segment = [
+                            (line_base + col) as i64,
+                            file_idx as i64,
+                            line as i64,
+                            col as i64,
+                        ];
+                        if !first_on_line {
+                            mappings.push(',');
+                        }
+                        for (field, last) in segment.iter().zip(&mut last) {
+                            write_vlq(&mut mappings, field - *last);
+                            *last = *field;
+                        }
+                        first_on_line = false;
+                    }
+                    after_space = c.is_whitespace();
+                    col += c.len_utf16();
+                }
+                if text.ends_with('\n') {
+                    mappings.push(';');
+                    line_base = 0;
+                    last[0] = 0;
+                    first_on_line = true;
+                } else {
+                    line_base += col;
+                }
+            }
+        }
+
+        let map = SourceMap {
+            version: 3,
+            file,
+            sources: &self.file_names,
+            sources_content: self.files().map(|file| file.contents).collect(),
+            names: Vec::new(),
+            mappings,
+        };
+        serde_json::to_string(&map).expect("source maps are always valid JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_vlqs, write_vlq, SourceMapError, SourceMapFile};
+    use SourceFile;
+
+    #[test]
+    fn vlq() {
+        let mut out = String::new();
+        for &value in &[0, 1, -1, 15, 16, -2, 1000] {
+            write_vlq(&mut out, value);
+            out.push(' ');
+        }
+        assert_eq!(out, "A C D e gB F w+B ");
+        assert_eq!(
+            read_vlqs("ACDegBFw+B").unwrap(),
+            vec![0, 1, -1, 15, 16, -2, 1000]
+        );
+        assert_eq!(read_vlqs("g"), None);
+        assert_eq!(read_vlqs("A*"), None);
+    }
+
+    #[test]
+    fn source_map() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a.js", "x y\n");
+        sourcefile.add_file_raw("b.js", "z");
+        sourcefile.add_file_raw("c.js", " 😀 w\n");
+        assert_eq!(
+            sourcefile.source_map("out.js"),
+            concat!(
+                r#"{"version":3,"file":"out.js","sources":["a.js","b.js","c.js"],"#,
+                r#""sourcesContent":["x y\n","z"," 😀 w\n"],"names":[],"#,
+                r#""mappings":"AAAA,EAAE;ACAF,CCAA,CAAC,GAAG;"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn import_source_map() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a.js", "let é = 1;\n");
+        sourcefile.add_file_raw("b.js", "  f(x)");
+        sourcefile.add_file_raw("c.js", " 😀 w\n");
+        let imported = SourceMapFile::from_json(
+            &sourcefile.source_map("out.js"),
+            sourcefile.contents.clone(),
+        )
+        .unwrap();
+        assert_eq!(imported.sources(), &["a.js", "b.js", "c.js"]);
+        for (offset, _) in sourcefile.contents.char_indices() {
+            let original = sourcefile.resolve_offset(offset).unwrap();
+            let at_token = offset == 0
+                || sourcefile.contents[..offset].ends_with(char::is_whitespace)
+                    && !sourcefile.contents[offset..].starts_with(char::is_whitespace);
+            if at_token {
+                assert_eq!(imported.resolve_offset(offset), Some(original));
+            }
+        }
+        // Inside a token, the position is that of the token.
+        let in_token = imported.resolve_offset(15).unwrap();
+        assert_eq!(
+            (in_token.filename, in_token.col, in_token.offset),
+            ("b.js", 2, 15)
+        );
+        assert_eq!(imported.resolve_offset(sourcefile.contents.len()), None);
+    }
+
+    #[test]
+    fn import_errors() {
+        let map = |version: u32, mappings: &str| {
+            format!(
+                r#"{{"version":{},"sourceRoot":"src","sources":["a.js"],"mappings":"{}"}}"#,
+                version, mappings
+            )
+        };
+        let imported = SourceMapFile::from_json(&map(3, ";A,CAAC"), "\nxyz").unwrap();
+        // Without the source's text, columns stay in UTF-16 code units.
+        let y = imported.resolve_offset(2).unwrap();
+        assert_eq!((y.filename, y.line, y.col), ("src/a.js", 0, 1));
+        assert_eq!(imported.resolve_offset(1), None);
+        assert_eq!(imported.resolve_offset(0), None);
+
+        match SourceMapFile::from_json(&map(2, ""), "") {
+            Err(SourceMapError::Version(2)) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        match SourceMapFile::from_json(&map(3, ";AC"), "") {
+            Err(SourceMapError::Mappings { line: 1 }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        match SourceMapFile::from_json(&map(3, "ACAA"), "") {
+            Err(SourceMapError::Mappings { line: 0 }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        // Columns and source lines that overflow when the deltas are added up.
+        for mappings in &[
+            "qgggggggggggI,qgggggggggggI",
+            "AAqgggggggggggIA;AAqgggggggggggIA",
+        ] {
+            match SourceMapFile::from_json(&map(3, mappings), "") {
+                Err(SourceMapError::Mappings { .. }) => (),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert!(SourceMapFile::from_json("[]", "").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Resolving spans that end at the end of a file.
+
+use {Position, SourceFile, Span};
+
+/// How `SourceFile::resolve_offset_span_with` treats a span ending exactly at the end of the
+/// file it starts in.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum SpanEndPolicy {
+    /// Resolve the end like any other offset, as `resolve_offset_span` does. An end at the end
+    /// of `contents` doesn't resolve, and an end at the end of any other file resolves to the
+    /// start of the next one.
+    #[default]
+    Strict,
+    /// Resolve the end to the end of the file the span starts in: one past its last char, on
+    /// its last line or, if the file ends with a newline, on the line after. This suits spans
+    /// for errors like an unexpected end of file.
+    EndOfFile,
+}
+
+impl SourceFile {
+    /// Like `resolve_offset_span`, but choosing how to resolve an end at the end of a file.
+    pub fn resolve_offset_span_with<'a>(
+        &'a self,
+        start: usize,
+        end: usize,
+        policy: SpanEndPolicy,
+    ) -> Option<Span<'a>> {
+        if policy == SpanEndPolicy::Strict || end < start {
+            return self.resolve_offset_span(start, end);
+        }
+        let (line_idx, _) = self.locate_line(start)?;
+        let (file_idx, file_start) = self.locate_file(line_idx);
+        let file_end_line = self.file_line_ends[file_idx];
+        if end != self.line_offset(file_end_line) {
+            return self.resolve_offset_span(start, end);
+        }
+
+        let last_line = file_end_line - 1;
+        let last_start = self.line_offset(last_line);
+        let (line, col) = if self.contents[last_start..end].ends_with('\n') {
+            (file_end_line - file_start, 0)
+        } else {
+            (last_line - file_start, end - last_start)
+        };
+        Some(Span {
+            start: self.resolve_offset(start)?,
+            end: Position::new(&self.file_names[file_idx], line, col, end),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpanEndPolicy;
+    use SourceFile;
+
+    #[test]
+    fn end_of_file() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo");
+        sourcefile.add_file_raw("b", "three\n");
+        let eof = SpanEndPolicy::EndOfFile;
+
+        let span = sourcefile.resolve_offset_span_with(4, 7, eof).unwrap();
+        assert_eq!(
+            (span.end.filename, span.end.line, span.end.col),
+            ("a", 1, 3)
+        );
+        let strict = sourcefile
+            .resolve_offset_span_with(4, 7, SpanEndPolicy::Strict)
+            .unwrap();
+        assert_eq!((strict.end.filename, strict.end.line), ("b", 0));
+
+        let span = sourcefile.resolve_offset_span_with(7, 13, eof).unwrap();
+        assert_eq!(
+            (span.end.filename, span.end.line, span.end.col),
+            ("b", 1, 0)
+        );
+        assert!(sourcefile.resolve_offset_span(7, 13).is_none());
+
+        // Ends elsewhere resolve as usual.
+        assert_eq!(
+            sourcefile.resolve_offset_span_with(0, 2, eof),
+            sourcefile.resolve_offset_span(0, 2)
+        );
+        assert!(sourcefile.resolve_offset_span_with(0, 14, eof).is_none());
+    }
+}
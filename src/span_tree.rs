@@ -0,0 +1,177 @@
+//! Organising raw spans by containment.
+
+/// A batch of raw `(start, end)` spans arranged into a forest by containment.
+///
+/// Spans are referred to by their index in the batch passed to `new`. A span's parent is the
+/// innermost other span containing it, where `(start, end)` contains `(s, e)` if
+/// `start <= s && e <= end`:
+///
+///  - Of two equal spans, the one that came first in the batch is the parent of the other.
+///  - Spans that partially overlap don't contain each other. A span inside both comes under the
+///    one that starts later, so walking up from it doesn't reach the other one; the offset
+///    queries below still find every span.
+///  - Empty spans can be children, but contain no offsets.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SpanTree {
+    spans: Vec<(usize, usize)>,
+    /// The indices of the spans sorted by start, then longest first, then by index.
+    order: Vec<usize>,
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+}
+
+impl SpanTree {
+    /// Arrange a batch of spans.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if a span ends before it starts.
+    pub fn new<I>(spans: I) -> SpanTree
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        let spans: Vec<(usize, usize)> = spans.into_iter().collect();
+        for &(start, end) in &spans {
+            assert!(
+                start <= end,
+                "span {}..{} ends before it starts",
+                start,
+                end
+            );
+        }
+        let mut order: Vec<usize> = (0..spans.len()).collect();
+        order.sort_by_key(|&idx| (spans[idx].0, std::cmp::Reverse(spans[idx].1)));
+
+        let mut parents = vec![None; spans.len()];
+        let mut children = vec![Vec::new(); spans.len()];
+        let mut roots = Vec::new();
+        // Earlier spans that may still contain later ones, in sorted order.
+        let mut open: Vec<usize> = Vec::new();
+        for &idx in &order {
+            let (start, end) = spans[idx];
+            open.retain(|&other| spans[other].1 >= start);
+            // Containing spans start no later, so the last of them in sorted order is innermost.
+            match open.iter().rev().find(|&&other| spans[other].1 >= end) {
+                Some(&parent) => {
+                    parents[idx] = Some(parent);
+                    children[parent].push(idx);
+                }
+                None => roots.push(idx),
+            }
+            open.push(idx);
+        }
+        SpanTree {
+            spans,
+            order,
+            parents,
+            children,
+            roots,
+        }
+    }
+
+    /// The number of spans.
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Whether there are no spans.
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// The span at an index of the batch.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `idx` is out of bounds.
+    pub fn span(&self, idx: usize) -> (usize, usize) {
+        self.spans[idx]
+    }
+
+    /// The innermost span containing a span, if any.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `idx` is out of bounds.
+    pub fn parent(&self, idx: usize) -> Option<usize> {
+        self.parents[idx]
+    }
+
+    /// The spans whose parent is a span, in source order.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `idx` is out of bounds.
+    pub fn children(&self, idx: usize) -> &[usize] {
+        &self.children[idx]
+    }
+
+    /// The spans with no parent, in source order.
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    /// The spans containing `offset`, innermost first.
+    ///
+    /// With partially overlapping spans, those starting later come first.
+    pub fn ancestors_of(&self, offset: usize) -> Vec<usize> {
+        let started = self
+            .order
+            .partition_point(|&idx| self.spans[idx].0 <= offset);
+        self.order[..started]
+            .iter()
+            .rev()
+            .cloned()
+            .filter(|&idx| offset < self.spans[idx].1)
+            .collect()
+    }
+
+    /// The innermost span containing `offset`, if any.
+    pub fn innermost_at(&self, offset: usize) -> Option<usize> {
+        let started = self
+            .order
+            .partition_point(|&idx| self.spans[idx].0 <= offset);
+        self.order[..started]
+            .iter()
+            .rev()
+            .cloned()
+            .find(|&idx| offset < self.spans[idx].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpanTree;
+
+    #[test]
+    fn nested() {
+        // fn f() { if x { y } }
+        let tree = SpanTree::new(vec![(9, 19), (0, 21), (14, 17), (7, 21)]);
+        assert_eq!(tree.roots(), &[1]);
+        assert_eq!(tree.children(1), &[3]);
+        assert_eq!(tree.children(3), &[0]);
+        assert_eq!(tree.parent(2), Some(0));
+        assert_eq!(tree.ancestors_of(15), vec![2, 0, 3, 1]);
+        assert_eq!(tree.innermost_at(8), Some(3));
+        assert_eq!(tree.innermost_at(21), None);
+    }
+
+    #[test]
+    fn edge_cases() {
+        let tree = SpanTree::new(vec![(0, 10), (5, 15), (6, 8), (6, 8), (12, 12), (20, 20)]);
+        // Partial overlap: neither contains the other.
+        assert_eq!(tree.roots(), &[0, 1, 5]);
+        // Inside both, under the one starting later.
+        assert_eq!(tree.parent(2), Some(1));
+        assert_eq!(tree.ancestors_of(7), vec![3, 2, 1, 0]);
+        // Equal spans nest in batch order.
+        assert_eq!(tree.parent(3), Some(2));
+        // Empty spans nest but contain nothing.
+        assert_eq!(tree.parent(4), Some(1));
+        assert_eq!(tree.innermost_at(12), Some(1));
+        assert_eq!(tree.innermost_at(20), None);
+
+        assert!(SpanTree::new(Vec::new()).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+//! Errors that remember where in the source they happened.
+
+use std::{error, fmt};
+
+use {SourceFile, Span};
+
+/// An error together with the raw span of source it is about.
+///
+/// Library code can return this through `?` without access to the `SourceFile`, and the caller
+/// that owns it can resolve and display the location at the end, with `display`.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SpannedError<E> {
+    error: E,
+    start: usize,
+    end: usize,
+}
+
+impl<E> SpannedError<E> {
+    /// Attach the span `start..end` to an error.
+    pub fn new(error: E, start: usize, end: usize) -> Self {
+        SpannedError { error, start, end }
+    }
+
+    /// The raw `(start, end)` span.
+    pub fn span(&self) -> (usize, usize) {
+        (self.start, self.end)
+    }
+
+    /// The underlying error.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Discard the span, returning the underlying error.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+
+    /// Resolve the span against the `SourceFile` it came from.
+    pub fn resolve<'a>(&self, sourcefile: &'a SourceFile) -> Option<Span<'a>> {
+        sourcefile.resolve_offset_span(self.start, self.end)
+    }
+
+    /// Display the error prefixed by its location, as `path:line:col: message`.
+    ///
+    /// Lines and columns are shown 1-indexed. If the span can't be resolved, the raw offsets are
+    /// shown instead.
+    pub fn display<'a>(&'a self, sourcefile: &'a SourceFile) -> impl fmt::Display + 'a
+    where
+        E: fmt::Display,
+    {
+        DisplaySpanned {
+            error: self,
+            sourcefile,
+        }
+    }
+}
+
+struct DisplaySpanned<'a, E: 'a> {
+    error: &'a SpannedError<E>,
+    sourcefile: &'a SourceFile,
+}
+
+impl<'a, E: fmt::Display> fmt::Display for DisplaySpanned<'a, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.error.resolve(self.sourcefile) {
+            Some(span) => write!(
+                f,
+                "{}:{}:{}: {}",
+                span.start.filename,
+                span.start.line + 1,
+                span.start.col + 1,
+                self.error.error
+            ),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for SpannedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at bytes {}..{})", self.error, self.start, self.end)
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for SpannedError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpannedError;
+    use std::error::Error;
+    use std::num::ParseIntError;
+    use SourceFile;
+
+    fn parse(source: &str, start: usize, end: usize) -> Result<u32, SpannedError<ParseIntError>> {
+        source[start..end]
+            .parse()
+            .map_err(|e| SpannedError::new(e, start, end))
+    }
+
+    #[test]
+    fn spanned_error() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("nums.txt", "12\nx4\n");
+        assert_eq!(parse(&sourcefile.contents, 0, 2), Ok(12));
+
+        let err = parse(&sourcefile.contents, 3, 5).unwrap_err();
+        assert!(err.source().is_some());
+        assert_eq!(err.span(), (3, 5));
+        assert_eq!(
+            err.display(&sourcefile).to_string(),
+            "nums.txt:2:1: invalid digit found in string"
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid digit found in string (at bytes 3..5)"
+        );
+        let boxed: Box<dyn Error> = Box::new(err);
+        assert!(boxed.to_string().ends_with("3..5)"));
+    }
+}
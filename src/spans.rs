@@ -0,0 +1,75 @@
+//! Utilities for working with batches of raw spans.
+
+use {FileId, SourceFile};
+
+impl SourceFile {
+    /// Sort items carrying raw `(start, end)` spans into source order, and group them by the file
+    /// their span starts in.
+    ///
+    /// `span_of` extracts the span from an item, so this works for bare spans and for
+    /// `(span, payload)` pairs alike. Returns one slice per file that has any items, in file
+    /// order. Items starting past the end of `contents` are sorted to the end and left out of
+    /// every group.
+    pub fn sort_spans_in_place<'s, T, F>(
+        &self,
+        items: &'s mut [T],
+        span_of: F,
+    ) -> Vec<(FileId, &'s [T])>
+    where
+        F: Fn(&T) -> (usize, usize),
+    {
+        items.sort_by_key(|item| span_of(item));
+        let mut items: &'s [T] = items;
+        let mut groups = Vec::new();
+        for file in self.files() {
+            let count = items
+                .iter()
+                .take_while(|item| span_of(item).0 < file.range.end)
+                .count();
+            if count > 0 {
+                let (group, rest) = items.split_at(count);
+                groups.push((file.id, group));
+                items = rest;
+            }
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use SourceFile;
+
+    #[test]
+    fn sort_spans() {
+        let mut sourcefile = SourceFile::new();
+        let a = sourcefile.add_file_raw("a", "aaaa\n");
+        sourcefile.add_file_raw("b", "bbbb\n");
+        let c = sourcefile.add_file_raw("c", "cccc\n");
+
+        let mut spans = vec![
+            ((12, 13), "c2"),
+            ((1, 2), "a2"),
+            ((10, 11), "c1"),
+            ((0, 3), "a1"),
+            ((99, 100), "x"),
+        ];
+        let groups = sourcefile.sort_spans_in_place(&mut spans, |&(span, _)| span);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, a);
+        assert_eq!(
+            groups[0].1.iter().map(|&(_, p)| p).collect::<Vec<_>>(),
+            vec!["a1", "a2"]
+        );
+        assert_eq!(groups[1].0, c);
+        assert_eq!(
+            groups[1].1.iter().map(|&(_, p)| p).collect::<Vec<_>>(),
+            vec!["c1", "c2"]
+        );
+
+        let mut bare = vec![(6, 7), (0, 1)];
+        let groups = sourcefile.sort_spans_in_place(&mut bare, |&span| span);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(bare, vec![(0, 1), (6, 7)]);
+    }
+}
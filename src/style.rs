@@ -0,0 +1,162 @@
+//! Guessing the formatting conventions of a file from its text.
+
+use std::collections::HashMap;
+
+use {FileId, SourceFile};
+
+/// How a file's lines are indented.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Indent {
+    /// One tab per level.
+    Tabs,
+    /// This many spaces per level.
+    Spaces(usize),
+}
+
+/// The line ending a file uses.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum LineEnding {
+    /// `\n`.
+    Lf,
+    /// `\r\n`.
+    CrLf,
+}
+
+impl LineEnding {
+    /// The line ending as text.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// The formatting conventions of a file, see `SourceFile::file_info`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct FileStyle {
+    /// The indentation most of the file uses, if any lines are indented.
+    pub indent: Option<Indent>,
+    /// The line ending most of the file uses, if it has any line endings.
+    pub line_ending: Option<LineEnding>,
+}
+
+impl SourceFile {
+    /// Guess the indentation and line ending a file uses, so that generated text such as
+    /// suggested fixes can match it.
+    ///
+    /// The most common style wins. For spaces, the width is the most common change in
+    /// indentation between neighbouring non-blank lines, or the most common indentation if it
+    /// never changes, as when every indented line is indented the same. Nothing is recorded when files are
+    /// added, so this scans the file each time it is called.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`.
+    pub fn file_info(&self, id: FileId) -> FileStyle {
+        let (mut tabs, mut spaces) = (0, 0);
+        let (mut lf, mut crlf) = (0, 0);
+        let mut steps: HashMap<usize, usize> = HashMap::new();
+        let mut indents: HashMap<usize, usize> = HashMap::new();
+        let mut prev_indent = None;
+        for line_idx in self.file_line_indices(id) {
+            let line = &self.contents[self.line_offset(line_idx)..self.line_ends[line_idx]];
+            if line.ends_with("\r\n") {
+                crlf += 1;
+            } else if line.ends_with('\n') {
+                lf += 1;
+            }
+
+            let text = line.trim_end_matches(['\n', '\r']);
+            if text.trim().is_empty() {
+                continue;
+            }
+            if text.starts_with('\t') {
+                tabs += 1;
+                prev_indent = None;
+                continue;
+            }
+            let indent = text.len() - text.trim_start_matches(' ').len();
+            if indent > 0 {
+                spaces += 1;
+                *indents.entry(indent).or_insert(0) += 1;
+            }
+            if let Some(prev) = prev_indent {
+                let step = indent.abs_diff(prev);
+                if step > 0 {
+                    *steps.entry(step).or_insert(0) += 1;
+                }
+            }
+            prev_indent = Some(indent);
+        }
+
+        let indent = if tabs == 0 && spaces == 0 {
+            None
+        } else if tabs > spaces {
+            Some(Indent::Tabs)
+        } else {
+            most_common(steps)
+                .or_else(|| most_common(indents))
+                .map(Indent::Spaces)
+        };
+        let line_ending = if lf == 0 && crlf == 0 {
+            None
+        } else if crlf > lf {
+            Some(LineEnding::CrLf)
+        } else {
+            Some(LineEnding::Lf)
+        };
+        FileStyle {
+            indent,
+            line_ending,
+        }
+    }
+}
+
+/// The width counted most often, preferring the narrower on a tie, since it is likely the unit of
+/// the wider one.
+fn most_common(counts: HashMap<usize, usize>) -> Option<usize> {
+    counts
+        .into_iter()
+        .max_by(|&(a, a_count), &(b, b_count)| a_count.cmp(&b_count).then(b.cmp(&a)))
+        .map(|(width, _)| width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileStyle, Indent, LineEnding};
+    use SourceFile;
+
+    #[test]
+    fn file_info() {
+        let mut sourcefile = SourceFile::new();
+        let spaces =
+            sourcefile.add_file_raw("a.py", "def f():\r\n    if x:\r\n        y\r\n\r\n    z\n");
+        let tabs = sourcefile.add_file_raw("b.go", "func f() {\n\tx\n\t\ty\n}");
+        let flat = sourcefile.add_file_raw("c.txt", "one");
+        let even = sourcefile.add_file_raw("d.txt", "  a\n  b\n");
+
+        assert_eq!(
+            sourcefile.file_info(spaces),
+            FileStyle {
+                indent: Some(Indent::Spaces(4)),
+                line_ending: Some(LineEnding::CrLf),
+            }
+        );
+        assert_eq!(
+            sourcefile.file_info(tabs),
+            FileStyle {
+                indent: Some(Indent::Tabs),
+                line_ending: Some(LineEnding::Lf),
+            }
+        );
+        assert_eq!(sourcefile.file_info(even).indent, Some(Indent::Spaces(2)));
+        assert_eq!(
+            sourcefile.file_info(flat),
+            FileStyle {
+                indent: None,
+                line_ending: None,
+            }
+        );
+    }
+}
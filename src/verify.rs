@@ -0,0 +1,181 @@
+//! Checking the line index against the text it describes.
+
+use std::{error, fmt};
+
+use {FileId, Position, SourceFile};
+
+/// The first place where the line index disagrees with `contents`, see
+/// `SourceFile::verify_index`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct IndexDivergence {
+    /// The offset of the start of the line where the index and `contents` disagree.
+    pub offset: usize,
+    /// The length of that line according to the index, if the index has a line there.
+    pub indexed_len: Option<usize>,
+    /// The length of that line according to `contents`, if `contents` has a line there.
+    pub actual_len: Option<usize>,
+}
+
+impl fmt::Display for IndexDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let describe = |len: Option<usize>| match len {
+            Some(len) => format!("a line of {} bytes", len),
+            None => "no line".to_owned(),
+        };
+        write!(
+            f,
+            "line index has {} at offset {}, but contents has {}",
+            describe(self.indexed_len),
+            self.offset,
+            describe(self.actual_len)
+        )
+    }
+}
+
+impl error::Error for IndexDivergence {}
+
+impl SourceFile {
+    /// Re-derive the line index from `contents` and compare it with the stored one.
+    ///
+    /// This is a debugging aid: the two can only disagree if `contents` has been modified
+    /// directly. Each indexed line is checked against the newlines actually in `contents`. It is
+    /// O(length of `contents`).
+    pub fn verify_index(&self) -> Result<(), IndexDivergence> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("verify_index", bytes = self.contents.len()).entered();
+        // The length of the line starting at `offset`, according to `contents`.
+        let actual_len = |offset: usize| {
+            self.contents
+                .get(offset..)
+                .and_then(|rest| rest.split_inclusive('\n').next())
+                .map(str::len)
+        };
+
+        let mut offset = 0;
+        let mut line_idx = 0;
+        for &file_end in &self.file_line_ends {
+            let num_lines = file_end - line_idx;
+            for line in 0..num_lines {
+                let indexed_len = self.line_len(line_idx + line);
+                let actual = actual_len(offset);
+                // The last line of a file needn't end with a newline, so may be followed directly
+                // by the next file, but it must still end on a char boundary.
+                let matches = match actual {
+                    Some(len) if line + 1 == num_lines => {
+                        len == indexed_len
+                            || (len > indexed_len
+                                && self
+                                    .contents
+                                    .get(offset..offset + indexed_len)
+                                    .is_some_and(|text| !text.contains('\n')))
+                    }
+                    Some(len) => len == indexed_len,
+                    None => false,
+                };
+                if !matches {
+                    return Err(IndexDivergence {
+                        offset,
+                        indexed_len: Some(indexed_len),
+                        actual_len: actual,
+                    });
+                }
+                offset += indexed_len;
+            }
+            line_idx = file_end;
+        }
+        if offset != self.contents.len() {
+            return Err(IndexDivergence {
+                offset,
+                indexed_len: None,
+                actual_len: actual_len(offset),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that a `Position` from elsewhere, e.g. deserialized or typed in by a user, describes
+    /// a real place in this `SourceFile` before it is used to slice `contents`.
+    ///
+    /// The file must exist, the line must be one of its lines, and the column must be on a char
+    /// boundary within the line's text or just past it, as for `offset_of`. The offset must also
+    /// agree with the line and column. If several files have the position's name, any of them
+    /// will do.
+    pub fn verify_position(&self, position: &Position) -> bool {
+        self.file_names
+            .iter()
+            .enumerate()
+            .filter(|&(_, name)| name == position.filename)
+            .any(|(idx, _)| {
+                self.offset_of_line_col(FileId(idx), position.line, position.col)
+                    == Some(position.offset)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexDivergence;
+    use {Position, SourceFile};
+
+    #[test]
+    fn verify() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo\n");
+        sourcefile.add_file_raw("b", "three");
+        assert_eq!(sourcefile.verify_index(), Ok(()));
+
+        let mut edited = sourcefile.clone();
+        edited.contents.replace_range(1..2, "NN");
+        assert_eq!(
+            edited.verify_index(),
+            Err(IndexDivergence {
+                offset: 0,
+                indexed_len: Some(4),
+                actual_len: Some(5),
+            })
+        );
+
+        // The first file's line now ends inside the 'é'.
+        let mut inside_char = SourceFile::new();
+        inside_char.add_file_raw("a", "ab");
+        inside_char.add_file_raw("b", "x\n");
+        inside_char.contents.replace_range(1..2, "é");
+        assert_eq!(
+            inside_char.verify_index(),
+            Err(IndexDivergence {
+                offset: 0,
+                indexed_len: Some(2),
+                actual_len: Some(5),
+            })
+        );
+
+        let mut appended = sourcefile.clone();
+        appended.contents.push_str("\nfour\n");
+        assert_eq!(
+            appended.verify_index(),
+            Err(IndexDivergence {
+                offset: 13,
+                indexed_len: None,
+                actual_len: Some(1),
+            })
+        );
+    }
+
+    #[test]
+    fn verify_position() {
+        let mut sourcefile = SourceFile::new();
+        sourcefile.add_file_raw("a", "one\ntwo\n");
+        sourcefile.add_file_raw("b", "thrée");
+        let resolved = sourcefile.resolve_offset(9).unwrap();
+        assert!(sourcefile.verify_position(&resolved));
+        // Just past the end of a line's text is fine.
+        assert!(sourcefile.verify_position(&Position::new("a", 0, 3, 3)));
+
+        assert!(!sourcefile.verify_position(&Position::new("c", 0, 0, 0)));
+        assert!(!sourcefile.verify_position(&Position::new("a", 2, 0, 8)));
+        assert!(!sourcefile.verify_position(&Position::new("a", 0, 4, 4)));
+        assert!(!sourcefile.verify_position(&Position::new("b", 0, 4, 12)));
+        // The offset doesn't match the line and column.
+        assert!(!sourcefile.verify_position(&Position::new("a", 1, 0, 0)));
+    }
+}
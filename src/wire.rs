@@ -0,0 +1,291 @@
+//! A compact binary format for streams of spans with small payloads.
+
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+
+/// One span and its payload, as read by `SpanReader`.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SpanRecord {
+    /// The start of the span.
+    pub start: usize,
+    /// The end of the span.
+    pub end: usize,
+    /// The caller-defined bytes stored with the span.
+    pub payload: Vec<u8>,
+}
+
+/// Writes spans of `contents` with caller-defined payloads (e.g. diagnostic codes) compactly.
+///
+/// A record is written as three fields, each in bincode's varint integer encoding, so a record
+/// can also be read by bincode as `(i64, u64, Vec<u8>)`:
+///
+///  - the change in start offset since the previous record (or since 0), as a signed integer,
+///  - the length of the span,
+///  - the length of the payload, followed by the payload.
+///
+/// Integers below 251 take one byte, so streams sorted by start offset, where the changes are
+/// small, take about three bytes per record plus the payload. Unsorted streams still work, they
+/// are just less compact. The offsets read back can be resolved against the `SourceFile` they
+/// came from as usual.
+#[derive(Debug)]
+pub struct SpanWriter<W> {
+    writer: W,
+    last_start: usize,
+}
+
+impl<W: Write> SpanWriter<W> {
+    /// Write records to `writer`. Wrap it in a `BufWriter` if it is a file or socket.
+    pub fn new(writer: W) -> Self {
+        SpanWriter {
+            writer,
+            last_start: 0,
+        }
+    }
+
+    /// Write the span `start..end` with `payload`.
+    ///
+    /// Fails with `InvalidInput` if the span is reversed, or starts past `i64::MAX`, which the
+    /// format can't hold.
+    pub fn write(&mut self, start: usize, end: usize, payload: &[u8]) -> io::Result<()> {
+        if end < start {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "span end is before its start",
+            ));
+        }
+        let signed_start: i64 = start.try_into().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "span start is out of range")
+        })?;
+        // Both starts are at most `i64::MAX`, so the difference can't overflow.
+        let delta = signed_start - self.last_start as i64;
+        write_varint(&mut self.writer, zigzag(delta))?;
+        write_varint(&mut self.writer, (end - start) as u64)?;
+        write_varint(&mut self.writer, payload.len() as u64)?;
+        self.writer.write_all(payload)?;
+        self.last_start = start;
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the records written by a `SpanWriter`.
+///
+/// This is an iterator of records, ending at the end of the stream. A record cut short by the
+/// end of the stream is an `UnexpectedEof` error. The iterator also ends after any error, since
+/// the records after a bad one can't be found reliably.
+#[derive(Debug)]
+pub struct SpanReader<R> {
+    reader: R,
+    last_start: usize,
+    done: bool,
+}
+
+impl<R: Read> SpanReader<R> {
+    /// Read records from `reader`. Wrap it in a `BufReader` if it is a file or socket.
+    pub fn new(reader: R) -> Self {
+        SpanReader {
+            reader,
+            last_start: 0,
+            done: false,
+        }
+    }
+
+    fn read_record(&mut self, first: u8) -> io::Result<SpanRecord> {
+        let delta = unzigzag(read_varint_after(&mut self.reader, first)?);
+        // `last_start` came from an `i64`, so it fits back in one.
+        let start = (self.last_start as i64)
+            .checked_add(delta)
+            .ok_or_else(out_of_range)?;
+        let start = usize_from(start)?;
+        let len = usize_from(read_varint(&mut self.reader)?)?;
+        let end = start.checked_add(len).ok_or_else(out_of_range)?;
+        let payload_len = usize_from(read_varint(&mut self.reader)?)?;
+        let mut payload = Vec::new();
+        (&mut self.reader)
+            .take(payload_len as u64)
+            .read_to_end(&mut payload)?;
+        if payload.len() != payload_len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.last_start = start;
+        Ok(SpanRecord {
+            start,
+            end,
+            payload,
+        })
+    }
+}
+
+impl<R: Read> Iterator for SpanReader<R> {
+    type Item = io::Result<SpanRecord>;
+
+    fn next(&mut self) -> Option<io::Result<SpanRecord>> {
+        if self.done {
+            return None;
+        }
+        let mut first = [0];
+        let record = loop {
+            match self.reader.read(&mut first) {
+                Ok(0) => return None,
+                Ok(_) => break self.read_record(first[0]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e),
+            }
+        };
+        self.done = record.is_err();
+        Some(record)
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn usize_from<T: TryInto<usize>>(value: T) -> io::Result<usize> {
+    value.try_into().map_err(|_| out_of_range())
+}
+
+fn out_of_range() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "offset out of range")
+}
+
+/// Write `value` as one byte if it is below 251, else a marker byte and 2, 4 or 8 little-endian
+/// bytes, as bincode does.
+pub(crate) fn write_varint<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    if value < 251 {
+        writer.write_all(&[value as u8])
+    } else if value <= u64::from(u16::MAX) {
+        writer.write_all(&[251])?;
+        writer.write_all(&(value as u16).to_le_bytes())
+    } else if value <= u64::from(u32::MAX) {
+        writer.write_all(&[252])?;
+        writer.write_all(&(value as u32).to_le_bytes())
+    } else {
+        writer.write_all(&[253])?;
+        writer.write_all(&value.to_le_bytes())
+    }
+}
+
+pub(crate) fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut first = [0];
+    reader.read_exact(&mut first)?;
+    read_varint_after(reader, first[0])
+}
+
+/// Read the rest of a varint whose first byte has already been read.
+fn read_varint_after<R: Read>(reader: &mut R, first: u8) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    let len = match first {
+        0..=250 => return Ok(u64::from(first)),
+        251 => 2,
+        252 => 4,
+        253 => 8,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid varint marker",
+            ))
+        }
+    };
+    reader.read_exact(&mut bytes[..len])?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{SpanReader, SpanRecord, SpanWriter};
+
+    #[test]
+    fn round_trip() {
+        let records = vec![
+            SpanRecord {
+                start: 3,
+                end: 5,
+                payload: b"E01".to_vec(),
+            },
+            SpanRecord {
+                start: 10,
+                end: 10,
+                payload: Vec::new(),
+            },
+            SpanRecord {
+                start: 100_000,
+                end: 4_000_000_000,
+                payload: vec![7; 300],
+            },
+            SpanRecord {
+                start: 4,
+                end: 6,
+                payload: b"W".to_vec(),
+            },
+        ];
+        let mut writer = SpanWriter::new(Vec::new());
+        for record in &records {
+            writer
+                .write(record.start, record.end, &record.payload)
+                .unwrap();
+        }
+        let bytes = writer.into_inner();
+        // Sorted records with small payloads are 3 bytes plus the payload.
+        assert_eq!(&bytes[..6], &[6, 2, 3, b'E', b'0', b'1']);
+
+        let read: Vec<SpanRecord> = SpanReader::new(&bytes[..])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(read, records);
+
+        let truncated: Vec<_> = SpanReader::new(&bytes[..4]).collect();
+        assert_eq!(truncated.len(), 1);
+        assert_eq!(
+            truncated[0].as_ref().unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert!(SpanWriter::new(Vec::new()).write(2, 1, &[]).is_err());
+    }
+
+    #[test]
+    fn out_of_range() {
+        // A record starting at `i64::MAX`, as the zigzag varint `u64::MAX - 1`.
+        let max_start = [253, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0];
+        let streams: Vec<Vec<u8>> = vec![
+            // The next start is one past `i64::MAX`.
+            [&max_start[..], &[2, 0, 0]].concat(),
+            // The next start is before 0.
+            vec![1, 0, 0],
+            // The end overflows.
+            vec![
+                253, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 253, 0xff, 0xff, 0xff, 0xff,
+                0xff, 0xff, 0xff, 0xff, 0,
+            ],
+        ];
+        for bytes in streams {
+            let records: Vec<_> = SpanReader::new(&bytes[..]).collect();
+            // Nothing is read past the bad record.
+            let err = records.last().unwrap().as_ref().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+
+        // Starts past `i64::MAX` only exist where `usize` is 64 bits.
+        if cfg!(target_pointer_width = "64") {
+            let mut writer = SpanWriter::new(Vec::new());
+            let err = writer.write(usize::MAX - 1, usize::MAX, &[]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert!(writer.into_inner().is_empty());
+        }
+    }
+}
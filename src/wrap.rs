@@ -0,0 +1,118 @@
+//! Hard-wrapping long lines of source for narrow output.
+
+use {FileId, SourceFile};
+
+/// A line of text cut into rows of at most a fixed number of chars, see `WrappedLine::new`.
+///
+/// Columns are byte offsets, as in `Position`, so a column in the original line can be mapped to
+/// where it ended up with `locate`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct WrappedLine<'a> {
+    line: &'a str,
+    /// The byte offset in `line` where each row starts. The first is always 0.
+    row_starts: Vec<usize>,
+}
+
+impl<'a> WrappedLine<'a> {
+    /// Cut `line` into rows of `width` chars, with any remainder in a shorter last row.
+    ///
+    /// Rows are cut between chars, not words, so every char stays at a predictable place. An
+    /// empty line has one empty row.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `width` is 0.
+    pub fn new(line: &'a str, width: usize) -> WrappedLine<'a> {
+        assert!(width > 0, "can't wrap to a width of 0");
+        let mut row_starts = vec![0];
+        row_starts.extend(
+            line.char_indices()
+                .map(|(byte, _)| byte)
+                .enumerate()
+                .filter(|&(idx, _)| idx > 0 && idx % width == 0)
+                .map(|(_, byte)| byte),
+        );
+        WrappedLine { line, row_starts }
+    }
+
+    /// The number of rows.
+    pub fn row_count(&self) -> usize {
+        self.row_starts.len()
+    }
+
+    /// The text of a row, or `None` if there are fewer rows.
+    pub fn row(&self, row: usize) -> Option<&'a str> {
+        let start = *self.row_starts.get(row)?;
+        let end = self
+            .row_starts
+            .get(row + 1)
+            .cloned()
+            .unwrap_or(self.line.len());
+        Some(&self.line[start..end])
+    }
+
+    /// Iterate over the text of the rows.
+    pub fn rows<'w>(&'w self) -> impl Iterator<Item = &'a str> + 'w {
+        (0..self.row_count()).map(move |row| self.row(row).unwrap())
+    }
+
+    /// The row and column within it of a column of the original line.
+    ///
+    /// The column just past the end of the line is at the end of the last row, so a caret for
+    /// e.g. a missing `;` can still be placed. Returns `None` for columns further on or inside a
+    /// char.
+    pub fn locate(&self, col: usize) -> Option<(usize, usize)> {
+        if !self.line.is_char_boundary(col) {
+            return None;
+        }
+        let row = self.row_starts.partition_point(|&start| start <= col) - 1;
+        Some((row, col - self.row_starts[row]))
+    }
+}
+
+impl SourceFile {
+    /// Hard-wrap a 0-indexed line of a file, without its line ending, to `width` chars, or
+    /// `None` if the file has no such line.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` was not returned by this `SourceFile`, or if `width` is
+    /// 0.
+    pub fn wrap_line<'a>(
+        &'a self,
+        id: FileId,
+        line: usize,
+        width: usize,
+    ) -> Option<WrappedLine<'a>> {
+        Some(WrappedLine::new(self.line(id, line)?, width))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WrappedLine;
+    use SourceFile;
+
+    #[test]
+    fn wrap() {
+        let wrapped = WrappedLine::new("let résumé = 1;", 6);
+        let rows: Vec<_> = wrapped.rows().collect();
+        assert_eq!(rows, vec!["let ré", "sumé =", " 1;"]);
+        // The `=` is at byte 13 of the line.
+        assert_eq!(wrapped.locate(13), Some((1, 6)));
+        assert_eq!(wrapped.locate(0), Some((0, 0)));
+        assert_eq!(wrapped.locate(17), Some((2, 3)));
+        assert_eq!(wrapped.locate(18), None);
+        assert_eq!(wrapped.locate(6), None);
+
+        let empty = WrappedLine::new("", 4);
+        assert_eq!(empty.rows().collect::<Vec<_>>(), vec![""]);
+        assert_eq!(empty.locate(0), Some((0, 0)));
+
+        let mut sourcefile = SourceFile::new();
+        let id = sourcefile.add_file_raw("a", "abcdefg\r\n");
+        let wrapped = sourcefile.wrap_line(id, 0, 3).unwrap();
+        assert_eq!(wrapped.rows().collect::<Vec<_>>(), vec!["abc", "def", "g"]);
+        assert!(sourcefile.wrap_line(id, 1, 3).is_none());
+    }
+}